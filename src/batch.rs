@@ -0,0 +1,171 @@
+use std::io::Write;
+
+use pngme::json::{self, Json};
+
+use crate::{cli::{EncodeOptions, InputOptions, OutputOptions}, encode, load_png, save_png, Result};
+
+/// One edit from a batch manifest, e.g.
+/// `{"input": "a.png", "operation": "encode", "chunk_type": "ruSt", "message": "hi", "output": "b.png"}`.
+/// `output` defaults to overwriting `input`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub input: String,
+    pub operation: Operation,
+    pub output: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Operation {
+    Encode { chunk_type: String, message: String },
+    Remove { chunk_type: String },
+    Replace { chunk_type: String, message: String },
+}
+
+fn field<'a>(value: &'a Json, key: &str) -> Result<&'a str> {
+    value.get(key)
+        .and_then(Json::as_str)
+        .ok_or_else(|| format!("missing string field \"{}\"", key).into())
+}
+
+fn parse_entry(value: &Json) -> Result<Entry> {
+    let input = field(value, "input")?.to_string();
+    let chunk_type = field(value, "chunk_type")?.to_string();
+    let operation = match field(value, "operation")? {
+        "encode" => Operation::Encode { chunk_type, message: field(value, "message")?.to_string() },
+        "remove" => Operation::Remove { chunk_type },
+        "replace" => Operation::Replace { chunk_type, message: field(value, "message")?.to_string() },
+        other => return Err(format!("unknown operation \"{}\"", other).into())
+    };
+    let output = match value.get("output") {
+        Some(output) => output.as_str().ok_or("\"output\" must be a string")?.to_string(),
+        None => input.clone()
+    };
+    Ok(Entry { input, operation, output })
+}
+
+/// Applies one entry, returning the number of bytes written.
+fn apply(entry: &Entry) -> Result<usize> {
+    let mut png = load_png(entry.input.clone(), &InputOptions::default())?;
+    match &entry.operation {
+        Operation::Encode { chunk_type, message } => encode(&mut png, chunk_type, message.as_bytes(), &EncodeOptions::default())?,
+        Operation::Remove { chunk_type } => {
+            png.remove_chunk(chunk_type)?;
+            png.validate_structure()?;
+        },
+        Operation::Replace { chunk_type, message } => png.replace_chunk(chunk_type, message.as_bytes().to_vec())?,
+    }
+    save_png(&png, entry.output.clone(), &OutputOptions::default())
+}
+
+/// Runs every entry of the manifest at `path` in order. A failing entry is reported and skipped,
+/// unless `fail_fast` is set, in which case nothing after it runs.
+pub fn run(path: &str, fail_fast: bool, out: &mut impl Write) -> Result<()> {
+    let manifest = json::parse(&std::fs::read_to_string(path)?)?;
+    let entries = manifest.as_array().ok_or("manifest must be a JSON array of entries")?;
+
+    let mut failed = 0;
+    for (idx, value) in entries.iter().enumerate() {
+        match parse_entry(value).and_then(|entry| apply(&entry).map(|written| (entry, written))) {
+            Ok((entry, written)) => writeln!(out, "entry {}: wrote {} bytes to {}", idx, written, entry.output)?,
+            Err(err) => {
+                writeln!(out, "entry {} failed: {}", idx, err)?;
+                failed += 1;
+                if fail_fast {
+                    break;
+                }
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(format!("{} of {} manifest entries failed", failed, entries.len()).into());
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "fixtures"))]
+mod tests {
+    use super::*;
+    use pngme::{chunk::Chunk, chunk_type::ChunkType, fixtures::{self, ColorType}, png::Png};
+    use std::str::FromStr;
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("pngme-{}-{}", std::process::id(), name)).to_string_lossy().into_owned()
+    }
+
+    fn write_manifest(name: &str, entries: &[Json]) -> String {
+        let path = temp_path(name);
+        std::fs::write(&path, Json::Array(entries.to_vec()).to_string()).unwrap();
+        path
+    }
+
+    fn entry(fields: &[(&str, &str)]) -> Json {
+        Json::Object(fields.iter().map(|(key, value)| (key.to_string(), Json::from(*value))).collect())
+    }
+
+    #[test]
+    fn test_batch_two_operations() {
+        let input = temp_path("batch_input.png");
+        let output = temp_path("batch_output.png");
+        let mut png = fixtures::minimal_png(1, 1, ColorType::Rgb);
+        png.insert_before_iend(Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"old".to_vec())).unwrap();
+        std::fs::write(&input, png.as_bytes()).unwrap();
+
+        let manifest = write_manifest("batch_two.json", &[
+            entry(&[("input", &input), ("operation", "encode"), ("chunk_type", "meTa"), ("message", "added"), ("output", &output)]),
+            entry(&[("input", &output), ("operation", "replace"), ("chunk_type", "ruSt"), ("message", "new")]),
+        ]);
+
+        let mut out = Vec::new();
+        run(&manifest, false, &mut out).unwrap();
+
+        let result = Png::try_from(std::fs::read(&output).unwrap().as_slice()).unwrap();
+        assert_eq!(result.chunk_by_type("meTa").unwrap().data(), b"added");
+        assert_eq!(result.chunk_by_type("ruSt").unwrap().data(), b"new");
+        for path in [input, output, manifest] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_batch_reports_failed_entry() {
+        let input = temp_path("batch_failing.png");
+        std::fs::write(&input, fixtures::minimal_png(1, 1, ColorType::Rgb).as_bytes()).unwrap();
+
+        let entries = [
+            entry(&[("input", &input), ("operation", "remove"), ("chunk_type", "ruSt")]),
+            entry(&[("input", &input), ("operation", "encode"), ("chunk_type", "ruSt"), ("message", "later")]),
+        ];
+        let manifest = write_manifest("batch_failing.json", &entries);
+
+        let mut out = Vec::new();
+        assert!(run(&manifest, true, &mut out).is_err());
+        let output = String::from_utf8(out).unwrap();
+        assert!(output.contains("entry 0 failed: No chunk of type ruSt"));
+        assert!(!output.contains("entry 1"));
+
+        let mut out = Vec::new();
+        let err = run(&manifest, false, &mut out).unwrap_err();
+        assert_eq!(err.to_string(), "1 of 2 manifest entries failed");
+        let png = Png::try_from(std::fs::read(&input).unwrap().as_slice()).unwrap();
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), b"later");
+
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(manifest).unwrap();
+    }
+
+    #[test]
+    fn test_parse_entry() {
+        let value = entry(&[("input", "a.png"), ("operation", "remove"), ("chunk_type", "ruSt")]);
+        assert_eq!(parse_entry(&value).unwrap(), Entry {
+            input: String::from("a.png"),
+            operation: Operation::Remove { chunk_type: String::from("ruSt") },
+            output: String::from("a.png"),
+        });
+
+        let value = entry(&[("input", "a.png"), ("operation", "frobnicate"), ("chunk_type", "ruSt")]);
+        assert!(parse_entry(&value).unwrap_err().to_string().contains("unknown operation"));
+        let value = entry(&[("input", "a.png"), ("operation", "encode"), ("chunk_type", "ruSt")]);
+        assert!(parse_entry(&value).unwrap_err().to_string().contains("\"message\""));
+    }
+}
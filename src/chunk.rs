@@ -1,4 +1,4 @@
-use std::{fmt::Display, string::FromUtf8Error, io::{BufReader, Read}};
+use std::{fmt::Display, io::{self, Write}, string::FromUtf8Error};
 
 use crate::chunk_type::{ChunkType, ChunkTypeError};
 use crc::{Crc, CRC_32_ISO_HDLC};
@@ -7,22 +7,40 @@ use thiserror::Error;
 pub const CRC: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
 
 
+/// Read errors carry the byte offset of the field that couldn't be read. Chunks parse with
+/// offsets relative to their own start; [`crate::png::Png`] shifts them to file offsets.
+///
+/// More variants may be added, so matches outside this crate need a catch-all arm. Checking
+/// [`ChunkError::is_recoverable`] first usually covers what callers want to tell apart:
+///
+/// ```
+/// use pngme::chunk::{Chunk, ChunkError};
+///
+/// let describe = |err: &ChunkError| match err {
+///     err if err.is_recoverable() => "retry with Chunk::try_from_lenient",
+///     ChunkError::DataByteRead { .. } => "truncated data",
+///     _ => "unreadable",
+/// };
+/// assert_eq!(describe(&Chunk::try_from(&[0u8, 0][..]).err().unwrap()), "unreadable");
+/// ```
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum ChunkError {
-    #[error("Error reading length bytes")]
-    LengthByteRead,
-    #[error("Error reading Chunk Type bytes")]
-    ChunkTypeByteRead,
-    #[error("Error reading Data bytes")]
-    DataByteRead,
-    #[error("Error reading Crc bytes")]
-    CrcByteRead,
+    #[error("Error reading length bytes at offset {offset}")]
+    LengthByteRead { offset: usize },
+    #[error("Error reading Chunk Type bytes at offset {offset}")]
+    ChunkTypeByteRead { offset: usize },
+    #[error("Error reading Data bytes of {chunk_type} chunk at offset {offset}")]
+    DataByteRead { offset: usize, chunk_type: ChunkType },
+    #[error("Error reading Crc bytes of {chunk_type} chunk at offset {offset}")]
+    CrcByteRead { offset: usize, chunk_type: ChunkType },
     #[error("Crc does not match.")]
     CrcMismatch,
     #[error("Invalid Chunk Type: {0}")]
     ChunkTypeError(ChunkTypeError)
 }
 
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct Chunk{
     length: u32,
     chunk_type: ChunkType,
@@ -33,30 +51,46 @@ pub struct Chunk{
 impl Chunk {
 
     pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Chunk {
+        Chunk {
+            length: data.len() as u32,
+            chunk_type,
+            crc: crc_of(&chunk_type, &data),
+            chunk_data: data
+        }
 
-        let to_crc: Vec<u8> = vec!(chunk_type.0, chunk_type.1, chunk_type.2, chunk_type.3)
-            .iter()
-            .cloned()
-            .chain(data.iter().cloned())
-            .collect();
+    }
 
+    /// Builds a chunk that stores `crc` verbatim instead of computing it, e.g. to produce
+    /// deliberately corrupt files. Prefer [`Chunk::new`] otherwise.
+    pub fn new_with_crc(chunk_type: ChunkType, data: Vec<u8>, crc: u32) -> Chunk {
         Chunk {
             length: data.len() as u32,
             chunk_type,
             chunk_data: data,
-            crc: CRC.checksum(to_crc.as_slice())
+            crc
         }
-
     }
 
     pub fn length(&self) -> u32{
         self.length
     }
 
+    /// The number of data bytes actually held, which disagrees with `length()` for a chunk whose
+    /// tampered length field was repaired by [`Chunk::try_from_repairing_length`].
+    pub fn data_length(&self) -> usize {
+        self.chunk_data.len()
+    }
+
     pub fn chunk_type(&self) -> &ChunkType{
         &(self.chunk_type)
     }
 
+    /// The chunk type as a borrowed string, for comparisons like `chunk.type_str() == "IEND"`.
+    /// Empty for a type that isn't UTF-8, so it never equals the name of a real type.
+    pub fn type_str(&self) -> &str {
+        self.chunk_type.as_str().unwrap_or_default()
+    }
+
     pub fn data(&self) -> &[u8]{
         self.chunk_data.as_slice()
     }
@@ -69,6 +103,38 @@ impl Chunk {
         self.crc
     }
 
+    /// Replaces the data, updating the length and crc to match. The type is kept.
+    pub fn set_data(&mut self, data: Vec<u8>) {
+        self.length = data.len() as u32;
+        self.crc = crc_of(&self.chunk_type, &data);
+        self.chunk_data = data;
+    }
+
+    /// Whether the stored crc matches one recomputed over the type and data, which fails for
+    /// chunks built by [`Chunk::new_with_crc`] with a wrong crc.
+    pub fn checksum_matches(&self) -> bool {
+        self.crc == self.compute_crc()
+    }
+
+    /// Computes the crc over the type and data, streaming both into the digest rather than
+    /// copying them into one buffer first.
+    pub fn compute_crc(&self) -> u32 {
+        crc_of(&self.chunk_type, &self.chunk_data)
+    }
+
+    /// Whether this is one of the standard textual chunks: `tEXt`, `zTXt` or `iTXt`.
+    pub fn is_text_chunk(&self) -> bool {
+        ["tEXt", "zTXt", "iTXt"].contains(&self.type_str())
+    }
+
+    /// Writes the same bytes as [`Chunk::as_bytes`] straight to `w`, without building a `Vec`.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(&self.length.to_be_bytes())?;
+        w.write_all(&self.chunk_type.bytes())?;
+        w.write_all(&self.chunk_data)?;
+        w.write_all(&self.crc.to_be_bytes())
+    }
+
     pub fn as_bytes(&self) -> Vec<u8> {
         let res = self.length.to_be_bytes();
         res.as_slice().iter().clone()
@@ -82,58 +148,123 @@ impl Chunk {
 
 }
 
-impl TryFrom<&[u8]> for Chunk {
-    type Error = ChunkError;
-
-    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
-
-        let mut reader = BufReader::new(value);
+impl ChunkError {
+    /// Whether the chunk itself was read fine and only its crc is wrong, so parsing with
+    /// [`Chunk::try_from_lenient`] will succeed. Every other error comes from data that ends too
+    /// early or can't be a chunk at all.
+    pub fn is_recoverable(&self) -> bool {
+        matches!(self, ChunkError::CrcMismatch)
+    }
 
-        let mut buffer_32: [u8; 4] = [0; 4];
-        
-        if let Err(_) = reader.read_exact(&mut buffer_32) {
-            return Err(ChunkError::LengthByteRead);
+    /// Moves the offset of a read error along by `base`, for a chunk that starts `base` bytes
+    /// into a larger buffer.
+    pub(crate) fn at(self, base: usize) -> ChunkError {
+        match self {
+            ChunkError::LengthByteRead { offset } => ChunkError::LengthByteRead { offset: base + offset },
+            ChunkError::ChunkTypeByteRead { offset } => ChunkError::ChunkTypeByteRead { offset: base + offset },
+            ChunkError::DataByteRead { offset, chunk_type } => ChunkError::DataByteRead { offset: base + offset, chunk_type },
+            ChunkError::CrcByteRead { offset, chunk_type } => ChunkError::CrcByteRead { offset: base + offset, chunk_type },
+            err => err
         }
+    }
+}
 
+fn crc_of(chunk_type: &ChunkType, data: &[u8]) -> u32 {
+    let mut digest = CRC.digest();
+    digest.update(&chunk_type.bytes());
+    digest.update(data);
+    digest.finalize()
+}
 
-        let length:u32 = u32::from_be_bytes(buffer_32);
+impl Chunk {
+    /// Parses a chunk like `try_from`, but keeps the stored crc instead of failing when it doesn't
+    /// match, for files written by tools that get the crc wrong.
+    pub fn try_from_lenient(value: &[u8]) -> Result<Chunk, ChunkError> {
+        Chunk::parse(value, false)
+    }
 
-        if let Err(_) = reader.read_exact(&mut buffer_32) {
-            return Err(ChunkError::ChunkTypeByteRead);
+    /// Parses a chunk whose length field may have been tampered with. When the declared length
+    /// doesn't end at a matching crc, e.g. because it runs into the next chunk or past the end of
+    /// `value`, the data is taken to end at the first point followed by one. The chunk keeps the
+    /// declared length, so `length()` and `data_length()` then disagree.
+    pub fn try_from_repairing_length(value: &[u8]) -> Result<Chunk, ChunkError> {
+        let err = match Chunk::parse(value, true) {
+            Ok(chunk) => return Ok(chunk),
+            Err(err @ (ChunkError::CrcMismatch | ChunkError::DataByteRead { .. } | ChunkError::CrcByteRead { .. })) => err,
+            Err(err) => return Err(err)
+        };
+        // the length and type were readable, or parsing would have failed before the data
+        let declared = u32::from_be_bytes(value[..4].try_into().unwrap());
+        let chunk_type = ChunkType::from_bytes_unchecked(value[4..8].try_into().unwrap());
+        let body = &value[8..];
+
+        let mut digest = CRC.digest();
+        digest.update(&chunk_type.bytes());
+        for len in 0..body.len().saturating_sub(3) {
+            let crc = u32::from_be_bytes(body[len..len + 4].try_into().unwrap());
+            if digest.clone().finalize() == crc {
+                return Ok(Chunk { length: declared, chunk_type, chunk_data: body[..len].to_vec(), crc });
+            }
+            digest.update(&body[len..len + 1]);
         }
+        Err(err)
+    }
 
-        let chunk_type = ChunkType::try_from(buffer_32);
+    fn parse(value: &[u8], check_crc: bool) -> Result<Chunk, ChunkError> {
+        let (length, chunk_type, crc) = Chunk::parse_header(value, check_crc)?;
+        Ok(Chunk {
+            length,
+            chunk_type,
+            chunk_data: value[8..8 + length as usize].to_vec(),
+            crc
+        })
+    }
 
-        if let Ok(chunk_type) = chunk_type {
+    /// Reads and checks the length, type and crc without copying the data, which is the
+    /// `length` bytes starting at offset 8.
+    fn parse_header(value: &[u8], check_crc: bool) -> Result<(u32, ChunkType, u32), ChunkError> {
+        let length = value.get(..4).ok_or(ChunkError::LengthByteRead { offset: 0 })?;
+        let length = u32::from_be_bytes(length.try_into().unwrap());
 
-            let mut chunk_data:Vec<u8> = vec!(0; length as usize);
-            if let Err(_) = reader.read_exact(&mut chunk_data) {
+        let chunk_type: [u8; 4] = value.get(4..8).ok_or(ChunkError::ChunkTypeByteRead { offset: 4 })?.try_into().unwrap();
+        let chunk_type = ChunkType::try_from(chunk_type).map_err(ChunkError::ChunkTypeError)?;
 
-                return Err(ChunkError::DataByteRead);
-            }
+        let data_end = 8 + length as usize;
+        let data = value.get(8..data_end).ok_or(ChunkError::DataByteRead { offset: 8, chunk_type })?;
 
-            let actual_crc: u32 = CRC.checksum(&value[4..(8+length as usize)]);
+        let crc = value.get(data_end..data_end + 4).ok_or(ChunkError::CrcByteRead { offset: data_end, chunk_type })?;
+        let crc = u32::from_be_bytes(crc.try_into().unwrap());
 
-            if let Err(_) = reader.read_exact(&mut buffer_32) {
-                return Err(ChunkError::CrcByteRead);
-            }
-            let  expected_crc: u32 = u32::from_be_bytes(buffer_32);
+        if check_crc && crc_of(&chunk_type, data) != crc {
+            return Err(ChunkError::CrcMismatch);
+        }
+        Ok((length, chunk_type, crc))
+    }
+}
 
-            if actual_crc != expected_crc {
-                return Err(ChunkError::CrcMismatch);
-            }
+impl TryFrom<&[u8]> for Chunk {
+    type Error = ChunkError;
 
-            return Ok(Chunk {
-                length,
-                chunk_type,
-                chunk_data,
-                crc: actual_crc
-            });
-        }
-        else  {
-            return Err(ChunkError::ChunkTypeError(chunk_type.unwrap_err()));
-        }
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        Chunk::parse(value, true)
+    }
+}
+
+/// Parses like the `&[u8]` impl, but reuses the vector's buffer for the chunk data. Bytes after
+/// the crc are dropped.
+impl TryFrom<Vec<u8>> for Chunk {
+    type Error = ChunkError;
 
+    fn try_from(mut value: Vec<u8>) -> Result<Self, Self::Error> {
+        let (length, chunk_type, crc) = Chunk::parse_header(&value, true)?;
+        value.truncate(8 + length as usize);
+        value.drain(..8);
+        Ok(Chunk {
+            length,
+            chunk_type,
+            chunk_data: value,
+            crc
+        })
     }
 }
 
@@ -141,7 +272,10 @@ impl Display for Chunk{
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "Chunk {{",)?;
         writeln!(f, "  Length: {}", self.length())?;
-        writeln!(f, "  Type: {}", self.chunk_type())?;
+        match self.chunk_type().description() {
+            Some(description) => writeln!(f, "  Type: {} – {}", self.chunk_type(), description)?,
+            None => writeln!(f, "  Type: {}", self.chunk_type())?,
+        }
         writeln!(f, "  Data: {} bytes", self.data().len())?;
         writeln!(f, "  Crc: {}", self.crc())?;
         writeln!(f, "}}",)?;
@@ -183,6 +317,19 @@ mod tests {
         assert_eq!(chunk.crc(), 2882656334);
     }
 
+    #[test]
+    fn test_new_chunk_with_crc() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let data = "This is where your secret message will be!".as_bytes().to_vec();
+        let chunk = Chunk::new_with_crc(chunk_type, data, 12345);
+        assert_eq!(chunk.length(), 42);
+        assert_eq!(chunk.crc(), 12345);
+
+        let bytes = chunk.as_bytes();
+        assert_eq!(&bytes[bytes.len() - 4..], &12345u32.to_be_bytes());
+        assert!(matches!(Chunk::try_from(bytes.as_ref()), Err(ChunkError::CrcMismatch)));
+    }
+
     #[test]
     fn test_chunk_length() {
         let chunk = testing_chunk();
@@ -195,6 +342,30 @@ mod tests {
         assert_eq!(chunk.chunk_type().to_string(), String::from("RuSt"));
     }
 
+    #[test]
+    fn test_group_by_type_in_hash_map() {
+        let chunks = [
+            Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"a".to_vec()),
+            Chunk::new(ChunkType::from_str("teXt").unwrap(), b"b".to_vec()),
+            Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"c".to_vec()),
+        ];
+        let mut groups: std::collections::HashMap<ChunkType, Vec<&Chunk>> = std::collections::HashMap::new();
+        for chunk in chunks.iter() {
+            groups.entry(*chunk.chunk_type()).or_default().push(chunk);
+        }
+        assert_eq!(groups[&ChunkType::from_str("ruSt").unwrap()].len(), 2);
+
+        let unique: std::collections::HashSet<Chunk> = chunks.iter().chain(chunks.iter()).cloned().collect();
+        assert_eq!(unique.len(), 3);
+    }
+
+    #[test]
+    fn test_type_str() {
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"data".to_vec());
+        assert_eq!(chunk.type_str(), "ruSt");
+        assert_eq!(testing_chunk().type_str(), "RuSt");
+    }
+
     #[test]
     fn test_chunk_string() {
         let chunk = testing_chunk();
@@ -277,4 +448,111 @@ mod tests {
 
         let _chunk_string = format!("{}", chunk);
     }
+
+    #[test]
+    pub fn test_is_text_chunk() {
+        let text = Chunk::new(ChunkType::from_str("tEXt").unwrap(), b"Title\0dice".to_vec());
+        assert!(text.is_text_chunk());
+        assert!(!testing_chunk().is_text_chunk());
+    }
+
+    #[test]
+    pub fn test_display_includes_description() {
+        let known = Chunk::new(ChunkType::from_str("IDAT").unwrap(), vec![]);
+        assert!(format!("{}", known).contains("Type: IDAT – image data"));
+        assert!(format!("{}", testing_chunk()).contains("Type: RuSt\n"));
+    }
+
+    #[test]
+    pub fn test_chunk_from_owned_vec() {
+        let mut bytes = testing_chunk().as_bytes();
+        let chunk = Chunk::try_from(bytes.clone()).unwrap();
+        assert_eq!(chunk.as_bytes(), bytes);
+        assert_eq!(chunk.data_as_string().unwrap(), testing_chunk().data_as_string().unwrap());
+
+        bytes.extend(b"trailing");
+        assert_eq!(Chunk::try_from(bytes.clone()).unwrap().as_bytes(), testing_chunk().as_bytes());
+
+        let last = bytes.len() - 9;
+        bytes[last] ^= 1;
+        assert!(matches!(Chunk::try_from(bytes), Err(ChunkError::CrcMismatch)));
+        assert!(matches!(Chunk::try_from(vec![0, 0]), Err(ChunkError::LengthByteRead { offset: 0 })));
+    }
+
+    #[test]
+    fn test_try_from_repairing_length() {
+        let mut bytes = testing_chunk().as_bytes();
+        assert!(Chunk::try_from_repairing_length(&bytes).unwrap() == testing_chunk());
+
+        bytes[3] = 3;
+        let repaired = Chunk::try_from_repairing_length(&bytes).unwrap();
+        assert_eq!(repaired.length(), 3);
+        assert_eq!(repaired.data(), testing_chunk().data());
+        assert!(matches!(Chunk::try_from(bytes.as_slice()), Err(ChunkError::CrcMismatch)));
+
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert!(matches!(Chunk::try_from_repairing_length(&bytes), Err(ChunkError::CrcMismatch)));
+    }
+
+    #[test]
+    fn test_is_recoverable() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        assert!(ChunkError::CrcMismatch.is_recoverable());
+        assert!(!ChunkError::LengthByteRead { offset: 0 }.is_recoverable());
+        assert!(!ChunkError::ChunkTypeByteRead { offset: 4 }.is_recoverable());
+        assert!(!ChunkError::DataByteRead { offset: 8, chunk_type }.is_recoverable());
+        assert!(!ChunkError::CrcByteRead { offset: 8, chunk_type }.is_recoverable());
+        let invalid_type = ChunkType::try_from([b'R', b'u', b'1', b't']).unwrap_err();
+        assert!(!ChunkError::ChunkTypeError(invalid_type).is_recoverable());
+    }
+
+    #[test]
+    pub fn test_write_to_matches_as_bytes() {
+        let mut written = Vec::new();
+        testing_chunk().write_to(&mut written).unwrap();
+        assert_eq!(written, testing_chunk().as_bytes());
+    }
+
+    #[test]
+    pub fn test_set_data() {
+        let mut chunk = Chunk::new_with_crc(ChunkType::from_str("RuSt").unwrap(), b"hello".to_vec(), 0xdeadbeef);
+        chunk.set_data(b"a longer message".to_vec());
+
+        assert_eq!(chunk.length(), 16);
+        assert_eq!(chunk.data(), b"a longer message");
+        assert_eq!(chunk.chunk_type().to_string(), "RuSt");
+        assert!(chunk.checksum_matches());
+        assert_eq!(chunk.crc(), Chunk::new(ChunkType::from_str("RuSt").unwrap(), b"a longer message".to_vec()).crc());
+    }
+
+    #[test]
+    pub fn test_checksum_matches() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        assert!(Chunk::new(chunk_type, b"hello".to_vec()).checksum_matches());
+        assert!(testing_chunk().checksum_matches());
+        assert!(!Chunk::new_with_crc(chunk_type, b"hello".to_vec(), 0xdeadbeef).checksum_matches());
+    }
+
+    #[test]
+    pub fn test_compute_crc_matches_one_shot() {
+        let chunk_type = ChunkType::from_str("IDAT").unwrap();
+        let data: Vec<u8> = (0..1 << 20).map(|i| (i % 251) as u8).collect();
+        let one_shot: Vec<u8> = chunk_type.bytes().iter().chain(data.iter()).copied().collect();
+
+        let chunk = Chunk::new(chunk_type, data);
+        assert_eq!(chunk.compute_crc(), CRC.checksum(&one_shot));
+        assert_eq!(chunk.crc(), chunk.compute_crc());
+    }
+
+    #[test]
+    pub fn test_try_from_lenient_keeps_bad_crc() {
+        let bad = Chunk::new_with_crc(ChunkType::from_str("RuSt").unwrap(), b"hello".to_vec(), 0xdeadbeef).as_bytes();
+
+        assert!(matches!(Chunk::try_from(bad.as_ref()), Err(ChunkError::CrcMismatch)));
+        let chunk = Chunk::try_from_lenient(bad.as_ref()).unwrap();
+        assert_eq!(chunk.crc(), 0xdeadbeef);
+        assert!(!chunk.checksum_matches());
+        assert_eq!(chunk.as_bytes(), bad);
+    }
 }
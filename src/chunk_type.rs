@@ -1,10 +1,22 @@
 use std::{str::FromStr, fmt::Display};
 use thiserror::Error;
 
-#[derive(Debug, Eq, PartialEq)]
-pub struct ChunkType(pub u8,pub u8,pub u8,pub u8);
+/// A four-byte chunk type code. Types order lexicographically by their bytes, so
+/// uppercase (critical) letters sort before lowercase ones.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash)]
+pub struct ChunkType([u8; 4]);
+
+/// The property bits encoded in the case of a chunk type's four letters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkProperties {
+    pub critical: bool,
+    pub public: bool,
+    pub reserved_bit_valid: bool,
+    pub safe_to_copy: bool,
+}
 
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum ChunkTypeError{
     #[error("Invalid ChunkType")]
     Invalid,
@@ -14,47 +26,263 @@ pub enum ChunkTypeError{
 
 impl ChunkType {
 
+    pub const CRITICAL_TYPES: [&'static str; 4] = ["IHDR", "PLTE", "IDAT", "IEND"];
+
+    pub const STANDARD_TYPES: [&'static str; 25] = [
+        "IHDR", "PLTE", "IDAT", "IEND",
+        "cHRM", "cICP", "gAMA", "iCCP", "mDCV", "cLLI", "sBIT", "sRGB",
+        "bKGD", "hIST", "tRNS", "eXIf", "pHYs", "sPLT", "tIME",
+        "iTXt", "tEXt", "zTXt",
+        "acTL", "fcTL", "fdAT",
+    ];
+
+    /// Color-space chunks that must appear before the image data.
+    pub const COLOR_TYPES: [&'static str; 6] = ["cHRM", "cICP", "gAMA", "iCCP", "sBIT", "sRGB"];
+
+    /// Colour, metadata and text chunks, which the spec places before the image data. Frame chunks
+    /// like `fcTL` and `fdAT` aren't among them since they interleave with it.
+    pub const BEFORE_IDAT_TYPES: [&'static str; 18] = [
+        "cHRM", "cICP", "gAMA", "iCCP", "mDCV", "cLLI", "sBIT", "sRGB",
+        "bKGD", "hIST", "tRNS", "eXIf", "pHYs", "sPLT", "tIME",
+        "iTXt", "tEXt", "zTXt",
+    ];
+
+    /// Every chunk type defined by the PNG specification (including APNG).
+    pub fn standard_types() -> Vec<ChunkType> {
+        Self::STANDARD_TYPES.iter()
+            .map(|chunk_type| ChunkType::from_str(chunk_type).unwrap())
+            .collect()
+    }
+
+    /// Whether this is a standard PNG chunk type rather than a custom one.
+    pub fn is_known(&self) -> bool {
+        Self::STANDARD_TYPES.iter().any(|known| known.as_bytes() == self.0)
+    }
+
+    /// A short human-readable description of a standard chunk type.
+    pub fn description(&self) -> Option<&'static str> {
+        self.as_str().and_then(known_chunk_description)
+    }
+
     pub fn bytes(&self) -> [u8; 4] {
-        return [
-            self.0,
-            self.1,
-            self.2,
-            self.3
-        ]
+        self.0
     }
 
+    /// Whether all four bytes are ASCII letters and the reserved bit is clear.
     pub fn is_valid(&self) -> bool {
-        self.is_reserved_bit_valid()
+        self.bytes().iter().all(ChunkType::is_valid_byte) && self.is_reserved_bit_valid()
     }
 
+    /// Builds a chunk type from any four bytes, skipping every check `try_from` makes. The result
+    /// may violate the spec (e.g. `0xDEADBEEF`), so this is only for testing how decoders cope
+    /// with malformed files; pngme itself refuses to parse such chunks.
+    pub fn from_bytes_unchecked(bytes: [u8; 4]) -> ChunkType {
+        ChunkType(bytes)
+    }
+
+    /// Ancillary bit (bit 5 of the first byte): uppercase means critical, i.e. a decoder
+    /// that doesn't recognise the chunk must reject the image.
+    ///
+    /// ```
+    /// use pngme::chunk_type::ChunkType;
+    /// use std::str::FromStr;
+    ///
+    /// assert!(ChunkType::from_str("BKGD").unwrap().is_critical());
+    /// assert!(!ChunkType::from_str("bKGD").unwrap().is_critical());
+    /// ```
     pub fn is_critical(&self) -> bool {
-        (self.0 & 1 << 5 ) ==  0
+        (self.0[0] & 1 << 5 ) ==  0
     }
 
+    /// Private bit (bit 5 of the second byte): uppercase means the type is defined by the
+    /// PNG specification or registered, lowercase means it is private to an application.
+    ///
+    /// ```
+    /// use pngme::chunk_type::ChunkType;
+    /// use std::str::FromStr;
+    ///
+    /// assert!(ChunkType::from_str("BKGD").unwrap().is_public());
+    /// assert!(ChunkType::from_str("bKGD").unwrap().is_public());
+    /// assert!(!ChunkType::from_str("bkGD").unwrap().is_public());
+    /// ```
     pub fn is_public(&self) -> bool {
-        (self.1 & 1 << 5) ==  0
+        (self.0[1] & 1 << 5) ==  0
     }
 
+    /// Reserved bit (bit 5 of the third byte): must be uppercase in every conforming type.
+    ///
+    /// ```
+    /// use pngme::chunk_type::ChunkType;
+    /// use std::str::FromStr;
+    ///
+    /// assert!(ChunkType::from_str("BKGD").unwrap().is_reserved_bit_valid());
+    /// assert!(ChunkType::from_str("bKGD").unwrap().is_reserved_bit_valid());
+    /// assert!(!ChunkType::from_str("bKgD").unwrap().is_reserved_bit_valid());
+    /// ```
     pub fn is_reserved_bit_valid(&self) -> bool {
-        (self.2 & 1 << 5 ) ==  0
+        (self.0[2] & 1 << 5 ) ==  0
+    }
+
+    /// The same type with the reserved bit cleared, i.e. the third letter uppercased, so an
+    /// otherwise valid type like `Rust` becomes the conforming `RuSt`.
+    ///
+    /// ```
+    /// use pngme::chunk_type::ChunkType;
+    ///
+    /// assert_eq!(ChunkType::parse("Rust").unwrap().with_valid_reserved_bit().to_string(), "RuSt");
+    /// ```
+    pub fn with_valid_reserved_bit(self) -> ChunkType {
+        let [a, b, c, d] = self.0;
+        ChunkType([a, b, c & !(1 << 5), d])
     }
 
+    /// Safe-to-copy bit (bit 5 of the fourth byte): lowercase means an editor that doesn't
+    /// recognise the chunk may copy it into a modified image; uppercase means it depends on
+    /// the image data and must be dropped if critical chunks change.
+    ///
+    /// ```
+    /// use pngme::chunk_type::ChunkType;
+    /// use std::str::FromStr;
+    ///
+    /// assert!(!ChunkType::from_str("BKGD").unwrap().is_safe_to_copy());
+    /// assert!(!ChunkType::from_str("bKGD").unwrap().is_safe_to_copy());
+    /// assert!(ChunkType::from_str("bKGd").unwrap().is_safe_to_copy());
+    /// ```
     pub fn is_safe_to_copy(&self) -> bool {
-        (self.3 & 1 << 5 ) !=  0
+        (self.0[3] & 1 << 5 ) !=  0
     }
 
-    pub fn to_string(&self) -> String {
-        match core::str::from_utf8(&(self.bytes())) {
-            Ok(res) => res.to_string(),
-            Err(err) => err.to_string()
+    /// All four property bits at once.
+    ///
+    /// ```
+    /// use pngme::chunk_type::{ChunkProperties, ChunkType};
+    /// use std::str::FromStr;
+    ///
+    /// let properties = ChunkType::from_str("bKGD").unwrap().properties();
+    /// assert_eq!(properties, ChunkProperties {
+    ///     critical: false,
+    ///     public: true,
+    ///     reserved_bit_valid: true,
+    ///     safe_to_copy: false,
+    /// });
+    /// assert!(ChunkType::from_str("BKGD").unwrap().properties().critical);
+    /// ```
+    pub fn properties(&self) -> ChunkProperties {
+        ChunkProperties {
+            critical: self.is_critical(),
+            public: self.is_public(),
+            reserved_bit_valid: self.is_reserved_bit_valid(),
+            safe_to_copy: self.is_safe_to_copy(),
         }
     }
 
+    /// Whether this type matches `pattern`, where `*` matches any run of characters and `?`
+    /// matches exactly one. A pattern without wildcards must equal the type exactly, including case.
+    pub fn matches(&self, pattern: &str) -> bool {
+        glob_match(pattern.as_bytes(), &self.0)
+    }
+
+    /// The type as a string slice borrowed from its bytes, without allocating. `None` for a type
+    /// built by [`ChunkType::from_bytes_unchecked`] from bytes that aren't UTF-8, which `Display`
+    /// writes as escapes instead.
+    ///
+    /// ```
+    /// use pngme::chunk_type::ChunkType;
+    ///
+    /// assert_eq!(ChunkType::parse("ruSt").unwrap().as_str(), Some("ruSt"));
+    /// assert_eq!(ChunkType::from_bytes_unchecked([0xde, 0xad, 0xbe, 0xef]).as_str(), None);
+    /// ```
+    pub fn as_str(&self) -> Option<&str> {
+        std::str::from_utf8(&self.0).ok()
+    }
+
+    /// Parses a chunk type from four ASCII letters, exactly like [`FromStr`] but without needing
+    /// the trait in scope.
+    ///
+    /// ```
+    /// use pngme::chunk_type::ChunkType;
+    ///
+    /// let background = ChunkType::parse("bKGD").unwrap();
+    /// assert!(!background.is_critical());
+    /// assert!(ChunkType::parse("bKG").is_err());
+    /// ```
+    pub fn parse(s: &str) -> Result<ChunkType, ChunkTypeError> {
+        s.parse()
+    }
+
+    /// Whether both types spell the same letters regardless of case. Case carries the property
+    /// bits, so this is only for explicitly case-insensitive lookups like `decode --ignore-case`.
+    ///
+    /// ```
+    /// use pngme::chunk_type::ChunkType;
+    /// use std::str::FromStr;
+    ///
+    /// let stored = ChunkType::from_str("ruSt").unwrap();
+    /// assert!(stored.eq_ignore_case(&ChunkType::from_str("Rust").unwrap()));
+    /// assert_ne!(stored, ChunkType::from_str("Rust").unwrap());
+    /// ```
+    pub fn eq_ignore_case(&self, other: &ChunkType) -> bool {
+        self.bytes().eq_ignore_ascii_case(&other.bytes())
+    }
+
+    pub fn is_pattern(s: &str) -> bool {
+        s.contains(['*', '?'])
+    }
+
     pub fn is_valid_byte(val: &u8) -> bool {
-        match val {
-            65..=90 | 97..=122 => true,
-            _ => false
-        }
+        matches!(val, 65..=90 | 97..=122)
+    }
+}
+
+/// Descriptions of every type in [`ChunkType::STANDARD_TYPES`].
+const DESCRIPTIONS: [(&str, &str); 25] = [
+    ("IHDR", "image header"),
+    ("PLTE", "palette"),
+    ("IDAT", "image data"),
+    ("IEND", "image trailer"),
+    ("cHRM", "primary chromaticities"),
+    ("cICP", "coding-independent code points"),
+    ("gAMA", "image gamma"),
+    ("iCCP", "embedded ICC profile"),
+    ("mDCV", "mastering display color volume"),
+    ("cLLI", "content light level"),
+    ("sBIT", "significant bits"),
+    ("sRGB", "standard RGB color space"),
+    ("bKGD", "background color"),
+    ("hIST", "palette histogram"),
+    ("tRNS", "transparency"),
+    ("eXIf", "exif metadata"),
+    ("pHYs", "physical pixel dimensions"),
+    ("sPLT", "suggested palette"),
+    ("tIME", "last modification time"),
+    ("iTXt", "international textual data"),
+    ("tEXt", "textual data"),
+    ("zTXt", "compressed textual data"),
+    ("acTL", "animation control"),
+    ("fcTL", "frame control"),
+    ("fdAT", "frame data"),
+];
+
+/// Looks up the description of a standard chunk type, e.g. `IHDR` is the "image header".
+/// Custom types return `None`.
+pub fn known_chunk_description(chunk_type: &str) -> Option<&'static str> {
+    DESCRIPTIONS.iter()
+        .find(|(known, _)| *known == chunk_type)
+        .map(|(_, description)| *description)
+}
+
+/// Matches `value` against a glob `pattern` supporting `*` and `?`.
+pub fn matches_pattern(pattern: &str, value: &str) -> bool {
+    glob_match(pattern.as_bytes(), value.as_bytes())
+}
+
+fn glob_match(pattern: &[u8], value: &[u8]) -> bool {
+    match (pattern.first(), value.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => glob_match(&pattern[1..], value) || (!value.is_empty() && glob_match(pattern, &value[1..])),
+        (Some(b'?'), Some(_)) => glob_match(&pattern[1..], &value[1..]),
+        (Some(p), Some(v)) if p == v => glob_match(&pattern[1..], &value[1..]),
+        _ => false
     }
 }
 
@@ -62,7 +290,7 @@ impl TryFrom<[u8; 4]> for ChunkType{
     type Error = ChunkTypeError;
 
     fn try_from(value: [u8; 4]) -> Result<Self, Self::Error> {
-        let res = ChunkType(value[0], value[1], value[2], value[3]);
+        let res = ChunkType(value);
         if res.is_valid() {
             Ok(res)
         }
@@ -72,6 +300,18 @@ impl TryFrom<[u8; 4]> for ChunkType{
     }
 }
 
+// Round-trip invariant: for any four ASCII letters `s`, `ChunkType::from_str(s)?.to_string() == s`,
+// and for any bytes accepted by `try_from`, `ChunkType::try_from(bytes)?.bytes() == bytes`.
+/// Parses four ASCII letters as-is. Case is never normalized: each letter's case is one of the
+/// property bits, so `ruSt` and `RuSt` are different chunk types.
+///
+/// ```
+/// use pngme::chunk_type::ChunkType;
+/// use std::str::FromStr;
+///
+/// assert_eq!(ChunkType::from_str("ruSt").unwrap().to_string(), "ruSt");
+/// assert_eq!("ruSt".parse::<ChunkType>().unwrap(), ChunkType::parse("ruSt").unwrap());
+/// ```
 impl FromStr for ChunkType{
     type Err = ChunkTypeError;
 
@@ -82,14 +322,36 @@ impl FromStr for ChunkType{
             Err(ChunkTypeError::InvalidString)
         }
         else {
-            Ok(ChunkType(byte_str[0],byte_str[1], byte_str[2], byte_str[3]))
+            Ok(ChunkType([byte_str[0], byte_str[1], byte_str[2], byte_str[3]]))
         }
     }
 }
 
+impl From<ChunkType> for [u8; 4] {
+    fn from(chunk_type: ChunkType) -> Self {
+        chunk_type.bytes()
+    }
+}
+
+impl From<&ChunkType> for String {
+    fn from(chunk_type: &ChunkType) -> Self {
+        chunk_type.to_string()
+    }
+}
+
+impl AsRef<[u8]> for ChunkType {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
 impl Display for ChunkType{
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f,"{}", self.to_string() )
+        match self.as_str() {
+            Some(res) => write!(f, "{}", res),
+            // only reachable through from_bytes_unchecked
+            None => write!(f, "{}", self.0.escape_ascii())
+        }
     }
 }
 
@@ -99,6 +361,37 @@ mod tests {
     use std::convert::TryFrom;
     use std::str::FromStr;
 
+    #[test]
+    pub fn test_hash_set_dedups() {
+        let types: std::collections::HashSet<ChunkType> = ["ruSt", "IDAT", "ruSt", "RuSt", "IDAT"]
+            .iter()
+            .map(|chunk_type| ChunkType::from_str(chunk_type).unwrap())
+            .collect();
+        assert_eq!(types.len(), 3);
+        assert!(types.contains(&ChunkType::from_str("RuSt").unwrap()));
+    }
+
+    #[test]
+    pub fn test_as_str() {
+        assert_eq!(ChunkType::from_str("ruSt").unwrap().as_str(), Some("ruSt"));
+        let invalid = ChunkType::from_bytes_unchecked([0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(invalid.as_str(), None);
+        assert_eq!(invalid.to_string(), "\\xde\\xad\\xbe\\xef");
+        let utf8 = ChunkType::from_bytes_unchecked(*b"r\xc3\xa9S");
+        assert_eq!(utf8.as_str(), Some("r\u{e9}S"));
+        assert_eq!(utf8.to_string(), "r\u{e9}S");
+    }
+
+    #[test]
+    pub fn test_with_valid_reserved_bit() {
+        let chunk = ChunkType::from_str("Rust").unwrap();
+        assert!(!chunk.is_valid());
+        let fixed = chunk.with_valid_reserved_bit();
+        assert_eq!(fixed.to_string(), "RuSt");
+        assert!(fixed.is_valid());
+        assert_eq!(fixed.with_valid_reserved_bit(), fixed);
+    }
+
     #[test]
     pub fn test_chunk_type_from_bytes() {
         let expected = [82, 117, 83, 116];
@@ -177,6 +470,126 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    pub fn test_chunk_type_is_known() {
+        assert!(ChunkType::from_str("IHDR").unwrap().is_known());
+        assert!(ChunkType::from_str("tEXt").unwrap().is_known());
+        assert!(!ChunkType::from_str("ruSt").unwrap().is_known());
+    }
+
+    #[test]
+    pub fn test_standard_types_are_valid() {
+        let standard_types = ChunkType::standard_types();
+        assert_eq!(standard_types.len(), ChunkType::STANDARD_TYPES.len());
+        assert!(standard_types.iter().all(|chunk_type| chunk_type.is_valid() && chunk_type.is_known()));
+    }
+
+    #[test]
+    pub fn test_known_chunk_description() {
+        assert_eq!(known_chunk_description("IDAT"), Some("image data"));
+        assert_eq!(known_chunk_description("pHYs"), Some("physical pixel dimensions"));
+        assert_eq!(known_chunk_description("ruSt"), None);
+        assert_eq!(ChunkType::from_str("tEXt").unwrap().description(), Some("textual data"));
+    }
+
+    #[test]
+    pub fn test_descriptions_cover_standard_types() {
+        assert!(ChunkType::STANDARD_TYPES.iter().all(|chunk_type| known_chunk_description(chunk_type).is_some()));
+    }
+
+    #[test]
+    pub fn test_matches_pattern() {
+        assert!(matches_pattern("??01", "ms01"));
+        assert!(matches_pattern("??01", "xy01"));
+        assert!(!matches_pattern("??01", "ms02"));
+        assert!(matches_pattern("ms*", "ms01"));
+        assert!(matches_pattern("ms*", "ms02"));
+        assert!(!matches_pattern("ms*", "mx01"));
+    }
+
+    #[test]
+    pub fn test_chunk_type_matches_exact() {
+        let chunk = ChunkType::from_str("msAa").unwrap();
+        assert!(chunk.matches("msAa"));
+        assert!(!chunk.matches("msAb"));
+        assert!(!chunk.matches("msA"));
+        assert!(!chunk.matches("MSAA"));
+    }
+
+    #[test]
+    pub fn test_chunk_type_matches_star() {
+        assert!(ChunkType::from_str("msAa").unwrap().matches("ms*"));
+        assert!(ChunkType::from_str("msZz").unwrap().matches("*"));
+        assert!(ChunkType::from_str("ruSt").unwrap().matches("*St"));
+        assert!(ChunkType::from_str("ruSt").unwrap().matches("r*t"));
+        assert!(!ChunkType::from_str("ruSt").unwrap().matches("ms*"));
+    }
+
+    #[test]
+    pub fn test_chunk_type_matches_question_mark() {
+        assert!(ChunkType::from_str("abST").unwrap().matches("??ST"));
+        assert!(ChunkType::from_str("xyST").unwrap().matches("??ST"));
+        assert!(!ChunkType::from_str("xyST").unwrap().matches("???ST"));
+        assert!(!ChunkType::from_str("xySt").unwrap().matches("??ST"));
+    }
+
+    const LETTERS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+    // Small xorshift generator so the property tests are deterministic.
+    fn random_letters(seed: &mut u64) -> [u8; 4] {
+        let mut res = [0; 4];
+        for byte in res.iter_mut() {
+            *seed ^= *seed << 13;
+            *seed ^= *seed >> 7;
+            *seed ^= *seed << 17;
+            *byte = LETTERS[(*seed % LETTERS.len() as u64) as usize];
+        }
+        res
+    }
+
+    fn letter_combinations() -> Vec<[u8; 4]> {
+        let mut res = vec!();
+        for position in 0..4 {
+            for letter in LETTERS {
+                let mut bytes = *b"ruSt";
+                bytes[position] = *letter;
+                res.push(bytes);
+            }
+        }
+        let mut seed = 0x2545F4914F6CDD1D;
+        res.extend((0..20_000).map(|_| random_letters(&mut seed)));
+        res
+    }
+
+    #[test]
+    pub fn test_chunk_type_parse() {
+        let chunk_type = ChunkType::parse("bKGD").unwrap();
+        assert_eq!(chunk_type, ChunkType::from_str("bKGD").unwrap());
+        assert_eq!(chunk_type.bytes(), *b"bKGD");
+        assert!(ChunkType::parse("bK1D").is_err());
+    }
+
+    #[test]
+    pub fn test_chunk_type_str_round_trip() {
+        for bytes in letter_combinations() {
+            let s = std::str::from_utf8(&bytes).unwrap();
+            let chunk_type = ChunkType::from_str(s).unwrap();
+            assert_eq!(chunk_type.to_string(), s);
+            assert_eq!(format!("{}", chunk_type), s);
+            assert_eq!(chunk_type.bytes(), bytes);
+        }
+    }
+
+    #[test]
+    pub fn test_chunk_type_bytes_round_trip() {
+        for bytes in letter_combinations() {
+            match ChunkType::try_from(bytes) {
+                Ok(chunk_type) => assert_eq!(chunk_type.bytes(), bytes),
+                Err(_) => assert!(bytes[2].is_ascii_lowercase()),
+            }
+        }
+    }
+
     #[test]
     pub fn test_chunk_type_string() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
@@ -191,4 +604,36 @@ mod tests {
         let _are_chunks_equal = chunk_type_1 == chunk_type_2;
     }
 
+    #[test]
+    pub fn test_from_bytes_unchecked() {
+        let chunk_type = ChunkType::from_bytes_unchecked([0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(chunk_type.bytes(), [0xde, 0xad, 0xbe, 0xef]);
+        assert!(!chunk_type.is_valid());
+        assert!(ChunkType::try_from(chunk_type.bytes()).is_err());
+        assert_eq!(chunk_type.to_string(), "\\xde\\xad\\xbe\\xef");
+
+        let digits = ChunkType::from_bytes_unchecked(*b"12A4");
+        assert!(digits.is_reserved_bit_valid());
+        assert!(!digits.is_valid());
+        assert!(ChunkType::try_from(*b"12A4").is_err());
+
+        assert_eq!(ChunkType::from_bytes_unchecked(*b"RuSt"), ChunkType::from_str("RuSt").unwrap());
+    }
+
+    #[test]
+    pub fn test_chunk_type_conversions() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+
+        let bytes: [u8; 4] = chunk_type.into();
+        assert_eq!(bytes, [82, 117, 83, 116]);
+        assert_eq!(ChunkType::try_from(bytes).unwrap(), chunk_type);
+
+        let string = String::from(&chunk_type);
+        assert_eq!(string, "RuSt");
+        assert_eq!(ChunkType::from_str(&string).unwrap(), chunk_type);
+
+        assert_eq!(chunk_type.as_ref(), b"RuSt");
+        assert_eq!(chunk_type.as_ref().len(), 4);
+    }
+
 }
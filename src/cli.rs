@@ -1,4 +1,8 @@
-use clap::{Parser, Subcommand};
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum, builder::RangedU64ValueParser};
+
+use pngme::{encoding::Encoding, hexdump, verify};
+#[cfg(feature = "fixtures")]
+use pngme::fixtures::ColorType;
 
 
 #[derive(Debug, Parser)]
@@ -6,6 +10,84 @@ use clap::{Parser, Subcommand};
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+
+    /// Log what pngme is doing to stderr (-vv for per-chunk details)
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+
+    /// Report how long reading, parsing, editing and writing take on stderr
+    #[arg(long, global = true)]
+    pub timing: bool,
+
+    /// Make the edits of any command that modifies a png in memory and report the result without
+    /// writing any file
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    #[command(flatten)]
+    pub input: InputOptions,
+
+    #[command(flatten)]
+    pub output: OutputOptions,
+}
+
+
+/// How pngs are written back out.
+#[derive(Debug, Default, Args)]
+pub struct OutputOptions {
+
+    /// Show a progress bar on stderr while saving
+    #[arg(long, global = true)]
+    pub progress: bool,
+
+    /// Re-read the saved file and check that it parses before reporting success
+    #[arg(long, global = true)]
+    pub verify_output: bool,
+
+    /// Don't warn when the output path doesn't end in .png
+    #[arg(long, global = true)]
+    pub no_extension_check: bool,
+
+    /// Keep the access and modification times of a file that is overwritten in place
+    #[arg(long, global = true)]
+    pub preserve_times: bool,
+
+    /// Give a new output file the same permissions as the input it was made from
+    #[arg(long, global = true)]
+    pub preserve_mode: bool,
+
+    /// Sort each run of ancillary chunks between critical chunks by type and data before saving,
+    /// so equivalent inputs produce byte-identical files
+    #[arg(long, global = true)]
+    pub canonical: bool,
+
+}
+
+
+/// Where to find the png inside the input file.
+#[derive(Debug, Default, Args)]
+pub struct InputOptions {
+
+    /// Read the png starting at byte N of the input file
+    #[arg(long, value_name = "N", global = true, conflicts_with = "scan")]
+    pub offset: Option<usize>,
+
+    /// Search the input file for an embedded png signature and read from there
+    #[arg(long, global = true)]
+    pub scan: bool,
+
+    /// Refuse to parse pngs with more than N chunks [default: 100000]
+    #[arg(long, value_name = "N", global = true)]
+    pub max_chunks: Option<usize>,
+
+    /// Load chunks whose crc doesn't match, keeping the stored crc
+    #[arg(long, global = true)]
+    pub no_crc_check: bool,
+
+    /// Recover chunks whose length field is wrong instead of failing, as `verify` does to report them
+    #[arg(skip)]
+    pub repair_lengths: bool,
+
 }
 
 
@@ -17,10 +99,16 @@ pub enum Commands{
 
         chunk_type: String,
 
+        /// The message to store, or `-` to read it from stdin until EOF. The png itself is
+        /// always read from `path`, so stdin only ever carries the message.
         message: String,
 
+        /// Output file, or `-` for stdout
         #[arg(default_value_t = String::from("output.png"))]
-        output_file: String
+        output_file: String,
+
+        #[command(flatten)]
+        options: EncodeOptions,
 
     },
 
@@ -28,22 +116,596 @@ pub enum Commands{
 
         path: String,
 
-        chunk_type: String,
+        #[arg(required_unless_present = "all")]
+        chunk_type: Option<String>,
+
+        #[command(flatten)]
+        options: DecodeOptions,
 
     },
     
+    /// Decode the chunk at a position in the file, counting from 0
+    DecodeAt {
+
+        path: String,
+
+        index: usize,
+
+        #[command(flatten)]
+        options: DecodeAtOptions,
+
+    },
+
     Remove {
 
         path: String,
 
-        chunk_type: String
+        chunk_type: String,
+
+        /// Match the chunk type regardless of letter case
+        #[arg(long)]
+        ignore_case: bool,
+
+        /// Remove the last chunk of the type instead of the first
+        #[arg(long)]
+        last: bool,
+
+        /// Remove every chunk of the type, which may be a `*`/`?` glob pattern
+        #[arg(long, conflicts_with = "last")]
+        all: bool,
+
+        /// Save even if the result is not a structurally valid png, or remove critical chunks with --all
+        #[arg(long)]
+        force: bool,
+
+        /// Overwrite the file without asking, even on a terminal
+        #[arg(short, long, conflicts_with = "no")]
+        yes: bool,
+
+        /// Answer no to the confirmation, leaving the file untouched
+        #[arg(long)]
+        no: bool,
+
+    },
+
+    /// Load a png once and edit it from an interactive prompt
+    Shell {
+
+        path: String,
+
+    },
+
+    /// List a png's chunks one per line
+    List {
+
+        path: String,
+
+        #[command(flatten)]
+        options: ListOptions,
+
+    },
+
+    /// Show the image properties stored in the IHDR chunk
+    Info {
+
+        path: String,
+
+    },
+
+    /// Check a png for structural problems
+    Verify {
+
+        path: String,
+
+        /// Also flag oddities most decoders tolerate, like chunks after IEND
+        #[arg(long)]
+        strict: bool,
+
+        /// Warn about ancillary chunks holding more than BYTES bytes of data
+        #[arg(long, value_name = "BYTES", default_value_t = verify::DEFAULT_WARN_SIZE)]
+        warn_size: u32,
+
+    },
+
+    /// Write a minimal valid png for testing
+    #[cfg(feature = "fixtures")]
+    #[command(hide = true)]
+    GenFixture {
+
+        #[arg(long, default_value_t = 1)]
+        width: u32,
+
+        #[arg(long, default_value_t = 1)]
+        height: u32,
+
+        #[arg(long, value_enum, default_value_t = ColorType::Rgba)]
+        color_type: ColorType,
+
+        #[arg(long, default_value_t = String::from("fixture.png"))]
+        output: String,
+
+    },
+
+    /// Replace the data of an existing chunk in place
+    Replace {
+
+        path: String,
+
+        chunk_type: String,
+
+        new_message: String,
+
+        /// Recompute the crc of every chunk in the file before saving
+        #[arg(long)]
+        recompute_all_crcs: bool,
+
+    },
+
+    /// Change a chunk's type while keeping its data and position
+    Retype {
+
+        path: String,
+
+        old_type: String,
+
+        new_type: String,
+
+        /// Uppercase the third letter of the new type instead of rejecting it, e.g. Rust becomes RuSt
+        #[arg(long)]
+        fix_reserved: bool,
+
+    },
+
+    /// Move text, metadata and colour chunks that come after the image data to just before the first IDAT
+    Reorder {
+
+        path: String,
+
+    },
+
+    /// Copy a chunk from one png into another, just before its IEND
+    Transfer {
+
+        src: String,
+
+        dst: String,
+
+        chunk_type: String,
+
+        /// Where to save the result; defaults to overwriting `dst`
+        #[arg(long)]
+        output: Option<String>,
+
+    },
+
+    /// Remove every ancillary chunk, leaving only the ones needed to display the image
+    Strip {
+
+        path: String,
+
+        /// Where to save the result; defaults to overwriting `path`
+        #[arg(long)]
+        output: Option<String>,
+
+        /// Keep ancillary chunks whose type matches CODE, which may be a glob; repeat to keep several
+        #[arg(long, value_name = "CODE")]
+        keep: Vec<String>,
+
+    },
+
+    /// Copy every ancillary chunk of a template png into another before its IEND
+    Merge {
+
+        base: String,
+
+        template: String,
+
+        /// Where to save the result; defaults to overwriting `base`
+        #[arg(long)]
+        output: Option<String>,
+
+        /// Replace chunks of a type the base already has instead of keeping them
+        #[arg(long)]
+        replace: bool,
+
+    },
+
+    /// Store UTF-8 text in an iTXt chunk, replacing any existing one with the same keyword
+    SetItxt {
+
+        path: String,
+
+        keyword: String,
+
+        text: String,
+
+        /// Language tag of the text, e.g. `en` or `ja`
+        #[arg(long, default_value_t = String::new())]
+        language: String,
+
+        /// The keyword translated into the text's language
+        #[arg(long, default_value_t = String::new())]
+        translated_keyword: String,
+
+        /// zlib-compress the text
+        #[arg(long)]
+        compress: bool,
+
+    },
+
+    /// Print the text of iTXt chunks; without a keyword every one is printed as `keyword: text`
+    GetItxt {
+
+        path: String,
+
+        keyword: Option<String>,
+
+    },
+
+    /// Store Latin-1 text in a zTXt chunk, replacing any existing one with the same keyword
+    SetZtxt {
+
+        path: String,
+
+        keyword: String,
+
+        value: String,
+
+    },
+
+    /// Print the text of the zTXt chunk with the given keyword
+    GetZtxt {
+
+        path: String,
+
+        keyword: String,
+
+    },
+
+    /// Set a key in the JSON object stored in the meTa chunk, creating the chunk if needed
+    MetaSet {
+
+        path: String,
+
+        key: String,
+
+        value: String,
+
+    },
+
+    /// Print the value of a key from the meTa chunk
+    MetaGet {
+
+        path: String,
+
+        key: String,
+
+    },
+
+    /// Print every key and value from the meTa chunk as `key: value`
+    MetaList {
+
+        path: String,
+
+    },
+
+    /// Write each chunk's data to its own file, named like `0001_ruSt.bin`
+    Extract {
+
+        path: String,
+
+        /// Directory to write into, created if missing
+        out_dir: String,
+
+        /// Only extract chunks whose type matches CODE, which may be a glob; repeat to allow several
+        #[arg(long = "type", value_name = "CODE")]
+        types: Vec<String>,
+
+    },
+
+    /// Salvage the readable chunks of a damaged png and report where parsing broke
+    Recover {
+
+        path: String,
+
+        /// Save the salvaged chunks, with an IEND added if it was lost, to this file
+        #[arg(long)]
+        output: Option<String>,
+
+    },
+
+    /// Compare the chunks of two pngs, matching the nth chunk of each type in both
+    Diff {
+
+        a: String,
+
+        b: String,
+
+    },
+
+    /// Apply the encode/remove/replace operations listed in a JSON manifest
+    Batch {
+
+        manifest: String,
+
+        /// Stop at the first failing entry instead of carrying on with the rest
+        #[arg(long)]
+        fail_fast: bool,
 
     },
 
     Print {
 
-        path: String
+        path: String,
+
+        #[command(flatten)]
+        options: PrintOptions,
 
     },
 
 }
+
+
+#[derive(Debug, Default, Args)]
+pub struct EncodeOptions {
+
+    /// Allow encoding into critical chunk types, and don't warn when the output path doesn't end
+    /// in .png
+    #[arg(long)]
+    pub force: bool,
+
+    /// Split the message across several chunks of at most SIZE bytes each
+    #[arg(long, value_name = "SIZE", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub split: Option<usize>,
+
+    /// Encoding applied to the message before it is stored
+    #[arg(long, value_enum, default_value_t = Encoding::Raw)]
+    pub encoding: Encoding,
+
+    /// Use these four bytes, e.g. 0xDEADBEEF, as the chunk type instead of the chunk_type argument,
+    /// even if they aren't letters. Such files violate the spec and pngme can't read them back.
+    #[arg(long, value_name = "HEX", value_parser = parse_raw_type)]
+    pub raw_type: Option<[u8; 4]>,
+
+    /// Name the output after the input instead of using output_file, e.g. "{stem}.encoded.{ext}".
+    /// Placeholders are {stem} and {ext} of the input file name and {type}, the chunk type; the
+    /// result is placed next to the input
+    #[arg(long, value_name = "TEMPLATE", conflicts_with = "output_file")]
+    pub output_template: Option<String>,
+
+    /// Write the result to PATH instead of output_file; repeat to write the same bytes to several
+    /// files
+    #[arg(long = "output", value_name = "PATH", conflicts_with_all = ["output_file", "output_template"])]
+    pub outputs: Vec<String>,
+
+    /// Print the size the output file would have, after --encoding, --split and gzip
+    /// compression, without writing it
+    #[arg(long)]
+    pub estimate: bool,
+
+    /// Treat the message as hex digits, e.g. 48656c6c6f, and store the bytes they spell
+    #[arg(long)]
+    pub hex: bool,
+
+    /// Don't warn when a message stored without --encoding isn't valid UTF-8
+    #[arg(long)]
+    pub allow_invalid_utf8: bool,
+
+    /// Recompute the crc of every chunk in the file before saving, not just the new one
+    #[arg(long)]
+    pub recompute_all_crcs: bool,
+
+    /// Fail instead of replacing the output file if it already exists
+    #[arg(long, conflicts_with = "overwrite")]
+    pub no_overwrite: bool,
+
+    /// Replace the output file if it already exists (the default)
+    #[arg(long)]
+    pub overwrite: bool,
+
+}
+
+
+fn parse_raw_type(value: &str) -> Result<[u8; 4], String> {
+    let digits = value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")).unwrap_or(value);
+    if digits.len() != 8 {
+        return Err(String::from("expected 8 hex digits, e.g. 0xDEADBEEF"));
+    }
+    u32::from_str_radix(digits, 16)
+        .map(u32::to_be_bytes)
+        .map_err(|err| err.to_string())
+}
+
+
+#[derive(Debug, Default, Args)]
+pub struct DecodeOptions {
+
+    /// Decode every chunk that holds valid UTF-8 text
+    #[arg(long, conflicts_with = "chunk_type")]
+    pub all: bool,
+
+    /// Include critical chunks when decoding with --all
+    #[arg(long, requires = "all")]
+    pub include_critical: bool,
+
+    /// Reassemble a message that was encoded with --split
+    #[arg(long, conflicts_with = "all")]
+    pub split: bool,
+
+    /// Encoding the message was stored with
+    #[arg(long, value_enum, default_value_t = Encoding::Raw)]
+    pub encoding: Encoding,
+
+    /// Match the chunk type regardless of letter case
+    #[arg(long)]
+    pub ignore_case: bool,
+
+    /// Print how many chunks matched instead of their messages
+    #[arg(long)]
+    pub count: bool,
+
+    /// Also decode chunks of pngs nested inside other chunks' data, printing each message with
+    /// the path of chunks that leads to it
+    #[arg(long, conflicts_with_all = ["all", "split"])]
+    pub recurse: bool,
+
+    /// How many levels of nested pngs --recurse descends into
+    #[arg(long, value_name = "N", default_value_t = 4, requires = "recurse")]
+    pub max_depth: usize,
+
+    /// Print each matching message as soon as its chunk is read, without loading the whole file
+    /// first. Ignores --offset, --scan and gzip compression
+    #[arg(long, conflicts_with_all = ["all", "split", "recurse", "ignore_case"])]
+    pub stream: bool,
+
+    /// With --stream, stop reading after the first matching message
+    #[arg(long, requires = "stream")]
+    pub first: bool,
+
+    /// Decode again each time the file changes on disk, until interrupted with Ctrl-C
+    #[arg(long, conflicts_with = "stream")]
+    pub watch: bool,
+
+    /// Copy the decoded message to the clipboard instead of printing it, falling back to printing
+    /// when no clipboard is available
+    #[cfg(feature = "clipboard")]
+    #[arg(long, conflicts_with = "count")]
+    pub clipboard: bool,
+
+}
+
+
+#[derive(Debug, Default, Args)]
+pub struct DecodeAtOptions {
+
+    /// Replace invalid UTF-8 with U+FFFD instead of failing
+    #[arg(long)]
+    pub lossy: bool,
+
+    /// Write the chunk's data bytes unchanged
+    #[arg(long, conflicts_with = "lossy")]
+    pub raw: bool,
+
+}
+
+
+#[derive(Debug, Args)]
+pub struct PrintOptions {
+
+    /// Dump each chunk's data as hex
+    #[arg(long)]
+    pub hexdump: bool,
+
+    /// Number of bytes per hexdump row
+    #[arg(long, default_value_t = hexdump::DEFAULT_WIDTH, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub hexdump_width: usize,
+
+    /// Maximum number of data bytes to dump per chunk
+    #[arg(long, default_value_t = hexdump::DEFAULT_MAX_BYTES)]
+    pub max_bytes: usize,
+
+    /// Only show chunks that aren't standard PNG chunk types
+    #[arg(long)]
+    pub custom: bool,
+
+    /// Only show the last N chunks
+    #[arg(long, value_name = "N")]
+    pub tail: Option<usize>,
+
+    /// When to color the output
+    #[arg(long, value_enum, default_value_t = ColorMode::Auto)]
+    pub color: ColorMode,
+
+    /// Show the SHA-256 of each chunk's data
+    #[arg(long)]
+    pub hash: bool,
+
+    /// Show each chunk's share of the file size, largest first, instead of the chunks themselves
+    #[arg(long)]
+    pub breakdown: bool,
+
+    /// Show how many chunks are critical, ancillary, public, private and safe to copy instead of
+    /// the chunks themselves
+    #[arg(long, conflicts_with = "breakdown")]
+    pub stats: bool,
+
+    /// Warn about ancillary chunks holding more than BYTES bytes of data
+    #[arg(long, value_name = "BYTES", default_value_t = verify::DEFAULT_WARN_SIZE)]
+    pub warn_size: u32,
+
+    /// Only show chunks whose type matches CODE, which may be a glob; repeat to allow several
+    #[arg(long = "type", value_name = "CODE")]
+    pub types: Vec<String>,
+
+    /// Show chunks sorted by type code instead of file order
+    #[arg(long)]
+    pub sort: bool,
+
+}
+
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorMode {
+    /// Color when writing to a terminal and `NO_COLOR` is unset
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ListFormat {
+    #[default]
+    Table,
+    /// One row per chunk: index,type,length,crc,critical,public,safe_to_copy,known
+    Csv,
+    /// An array with one object per chunk
+    Json,
+}
+
+
+#[derive(Debug, Default, Args)]
+pub struct ListOptions {
+
+    /// Group chunks under a header per chunk type (table format only)
+    #[arg(long)]
+    pub group_by_type: bool,
+
+    /// How to lay out the listing
+    #[arg(long, value_enum, default_value_t = ListFormat::Table)]
+    pub format: ListFormat,
+
+    /// Indent --format json output instead of writing it on one line
+    #[arg(long)]
+    pub pretty: bool,
+
+    /// Show the SHA-256 of each chunk's data
+    #[arg(long)]
+    pub hash: bool,
+
+    /// Only show chunks whose type matches CODE, which may be a glob; repeat to allow several
+    #[arg(long = "type", value_name = "CODE")]
+    pub types: Vec<String>,
+
+    /// Show chunks sorted by type code instead of file order
+    #[arg(long)]
+    pub sort: bool,
+
+    /// Show at most N chunks
+    #[arg(long, value_name = "N")]
+    pub limit: Option<usize>,
+
+    /// Skip the first M chunks (not --offset, which already picks where the png starts in the file)
+    #[arg(long, value_name = "M")]
+    pub skip: Option<usize>,
+
+    /// Show only the first N chunks; with --tail, both ends with the middle elided
+    #[arg(long, value_name = "N", conflicts_with_all = ["limit", "skip"])]
+    pub head: Option<usize>,
+
+    /// Show only the last N chunks; with --head, both ends with the middle elided
+    #[arg(long, value_name = "N", conflicts_with_all = ["limit", "skip"])]
+    pub tail: Option<usize>,
+
+}
@@ -0,0 +1,44 @@
+use std::{io::Write, process::{Command, Stdio}};
+
+/// A program that reads text to put on the clipboard from stdin, with its arguments.
+pub type Tool = (&'static str, &'static [&'static str]);
+
+#[cfg(target_os = "macos")]
+const TOOLS: &[Tool] = &[("pbcopy", &[])];
+#[cfg(windows)]
+const TOOLS: &[Tool] = &[("clip", &[])];
+#[cfg(not(any(target_os = "macos", windows)))]
+const TOOLS: &[Tool] = &[
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+];
+
+/// Puts `text` on the system clipboard with the first of the platform's clipboard tools that works.
+pub fn copy(text: &str) -> Result<(), String> {
+    copy_with(text, TOOLS)
+}
+
+/// Like [`copy`], trying `tools` in order. Fails if none can be run or all of them exit with an
+/// error, e.g. without a display server.
+pub fn copy_with(text: &str, tools: &[Tool]) -> Result<(), String> {
+    for (program, args) in tools {
+        let child = Command::new(program)
+            .args(*args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(_) => continue
+        };
+        // dropping stdin closes the pipe, which tells the tool the text is complete
+        let written = child.stdin.take().is_some_and(|mut stdin| stdin.write_all(text.as_bytes()).is_ok());
+        if child.wait().is_ok_and(|status| status.success()) && written {
+            return Ok(());
+        }
+    }
+    let names: Vec<&str> = tools.iter().map(|(program, _)| *program).collect();
+    Err(format!("no clipboard available (tried {})", names.join(", ")))
+}
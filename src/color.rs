@@ -0,0 +1,51 @@
+use std::io::Write;
+
+use pngme::chunk::Chunk;
+
+use crate::cli::ColorMode;
+
+const RED: &str = "31";
+const CYAN: &str = "36";
+const DIM: &str = "2";
+
+/// Wraps text in ANSI escape codes, or leaves it untouched when color is off.
+pub struct Palette {
+    enabled: bool,
+}
+
+impl Palette {
+    /// Resolves `mode`, where `auto` means color only on a terminal and only if `NO_COLOR` is unset.
+    pub fn new(mode: ColorMode, is_terminal: bool) -> Palette {
+        let enabled = match mode {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => is_terminal && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()),
+        };
+        Palette { enabled }
+    }
+
+    fn paint(&self, text: impl std::fmt::Display, code: &str) -> String {
+        if self.enabled {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        }
+        else {
+            text.to_string()
+        }
+    }
+}
+
+/// Writes `chunk` in the same layout as its `Display` impl, with the type and crc colorized.
+pub fn write_chunk(out: &mut impl Write, chunk: &Chunk, palette: &Palette) -> std::io::Result<()> {
+    let chunk_type = chunk.chunk_type();
+    let type_color = if chunk_type.is_critical() { RED } else { CYAN };
+
+    writeln!(out, "Chunk {{")?;
+    writeln!(out, "  Length: {}", chunk.length())?;
+    match chunk_type.description() {
+        Some(description) => writeln!(out, "  Type: {} – {}", palette.paint(chunk_type, type_color), description)?,
+        None => writeln!(out, "  Type: {}", palette.paint(chunk_type, type_color))?,
+    }
+    writeln!(out, "  Data: {} bytes", chunk.data().len())?;
+    writeln!(out, "  Crc: {}", palette.paint(chunk.crc(), DIM))?;
+    writeln!(out, "}}")
+}
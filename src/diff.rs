@@ -0,0 +1,147 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png};
+
+/// Where a chunk sits in its png, for reporting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkRef {
+    pub index: usize,
+    pub chunk_type: ChunkType,
+    pub length: u32,
+}
+
+impl ChunkRef {
+    fn new(index: usize, chunk: &Chunk) -> ChunkRef {
+        ChunkRef { index, chunk_type: *chunk.chunk_type(), length: chunk.length() }
+    }
+}
+
+impl Display for ChunkRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:>5}  {}  {:>10} bytes", self.index, self.chunk_type, self.length)
+    }
+}
+
+/// The chunks that differ between two pngs.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PngDiff {
+    pub only_in_a: Vec<ChunkRef>,
+    pub only_in_b: Vec<ChunkRef>,
+    /// Chunks present in both whose data or crc differ, as `(a, b)`.
+    pub changed: Vec<(ChunkRef, ChunkRef)>,
+}
+
+impl PngDiff {
+    pub fn is_empty(&self) -> bool {
+        self.only_in_a.is_empty() && self.only_in_b.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl Display for PngDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.only_in_a.is_empty() {
+            writeln!(f, "Only in a:")?;
+            for chunk in &self.only_in_a {
+                writeln!(f, "{}", chunk)?;
+            }
+        }
+        if !self.only_in_b.is_empty() {
+            writeln!(f, "Only in b:")?;
+            for chunk in &self.only_in_b {
+                writeln!(f, "{}", chunk)?;
+            }
+        }
+        if !self.changed.is_empty() {
+            writeln!(f, "Changed:")?;
+            for (a, b) in &self.changed {
+                writeln!(f, "{} -> {}", a, b)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compares the chunks of `a` and `b`. The nth chunk of a type in `a` is matched with the nth
+/// chunk of the same type in `b`, so reordering chunks of different types isn't a difference.
+pub fn diff(a: &Png, b: &Png) -> PngDiff {
+    let mut res = PngDiff::default();
+
+    let mut b_by_type: BTreeMap<ChunkType, Vec<(usize, &Chunk)>> = BTreeMap::new();
+    for (index, chunk) in b.chunks().iter().enumerate().rev() {
+        b_by_type.entry(*chunk.chunk_type()).or_default().push((index, chunk));
+    }
+
+    for (index, chunk) in a.chunks().iter().enumerate() {
+        match b_by_type.get_mut(chunk.chunk_type()).and_then(Vec::pop) {
+            Some((b_index, b_chunk)) => {
+                if chunk != b_chunk {
+                    res.changed.push((ChunkRef::new(index, chunk), ChunkRef::new(b_index, b_chunk)));
+                }
+            },
+            None => res.only_in_a.push(ChunkRef::new(index, chunk)),
+        }
+    }
+
+    res.only_in_b = b_by_type.into_values()
+        .flatten()
+        .map(|(index, chunk)| ChunkRef::new(index, chunk))
+        .collect();
+    res.only_in_b.sort_by_key(|chunk| chunk.index);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn png_from_chunks(chunks: &[(&str, &[u8])]) -> Png {
+        Png::from_chunks(chunks.iter()
+            .map(|(chunk_type, data)| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec()))
+            .collect())
+    }
+
+    fn chunk_ref(index: usize, chunk_type: &str, length: u32) -> ChunkRef {
+        ChunkRef { index, chunk_type: ChunkType::from_str(chunk_type).unwrap(), length }
+    }
+
+    #[test]
+    fn test_diff_identical() {
+        let png = png_from_chunks(&[("IHDR", &[0; 13]), ("IDAT", &[1, 2, 3]), ("IEND", &[])]);
+        assert!(diff(&png, &png).is_empty());
+        assert_eq!(diff(&png, &png).to_string(), "");
+    }
+
+    #[test]
+    fn test_diff_extra_chunk() {
+        let base = png_from_chunks(&[("IHDR", &[0; 13]), ("IDAT", &[1, 2, 3]), ("IEND", &[])]);
+        let edited = png_from_chunks(&[("IHDR", &[0; 13]), ("IDAT", &[1, 2, 3]), ("ruSt", b"hidden"), ("IEND", &[])]);
+
+        let res = diff(&base, &edited);
+        assert_eq!(res, PngDiff { only_in_b: vec![chunk_ref(2, "ruSt", 6)], ..Default::default() });
+        assert_eq!(res.to_string(), "Only in b:\n    2  ruSt           6 bytes\n");
+
+        let res = diff(&edited, &base);
+        assert_eq!(res.only_in_a, vec![chunk_ref(2, "ruSt", 6)]);
+        assert!(res.only_in_b.is_empty());
+    }
+
+    #[test]
+    fn test_diff_changed_data_matches_by_occurrence() {
+        let a = png_from_chunks(&[("IHDR", &[0; 13]), ("IDAT", &[1]), ("IDAT", &[2]), ("IEND", &[])]);
+        let b = png_from_chunks(&[("IHDR", &[0; 13]), ("IDAT", &[1]), ("IDAT", &[2, 2]), ("IDAT", &[3]), ("IEND", &[])]);
+
+        let res = diff(&a, &b);
+        assert_eq!(res.changed, vec![(chunk_ref(2, "IDAT", 1), chunk_ref(2, "IDAT", 2))]);
+        assert_eq!(res.only_in_b, vec![chunk_ref(3, "IDAT", 1)]);
+        assert!(res.only_in_a.is_empty());
+    }
+
+    #[test]
+    fn test_diff_changed_crc() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let a = Png::from_chunks(vec![Chunk::new(chunk_type, b"same".to_vec())]);
+        let b = Png::from_chunks(vec![Chunk::new_with_crc(chunk_type, b"same".to_vec(), 0)]);
+        assert_eq!(diff(&a, &b).changed.len(), 1);
+    }
+}
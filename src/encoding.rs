@@ -0,0 +1,174 @@
+use clap::ValueEnum;
+use thiserror::Error;
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// How a message's bytes are transformed before being stored in a chunk.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Encoding {
+    #[default]
+    Raw,
+    Base64,
+    Hex,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EncodingError {
+    #[error("Hex input has an odd number of digits")]
+    OddHexLength,
+    #[error("Invalid hex digit {0:?}")]
+    InvalidHexDigit(char),
+    #[error("Base64 input length is not a multiple of 4")]
+    InvalidBase64Length,
+    #[error("Invalid base64 character {0:?}")]
+    InvalidBase64Char(char),
+    #[error("Misplaced base64 padding")]
+    InvalidBase64Padding,
+}
+
+pub fn encode(data: &[u8], encoding: Encoding) -> Vec<u8> {
+    match encoding {
+        Encoding::Raw => data.to_vec(),
+        Encoding::Base64 => encode_base64(data),
+        Encoding::Hex => encode_hex(data),
+    }
+}
+
+pub fn decode(data: &[u8], encoding: Encoding) -> Result<Vec<u8>, EncodingError> {
+    match encoding {
+        Encoding::Raw => Ok(data.to_vec()),
+        Encoding::Base64 => decode_base64(data),
+        Encoding::Hex => decode_hex(data),
+    }
+}
+
+pub fn encode_hex(data: &[u8]) -> Vec<u8> {
+    data.iter()
+        .flat_map(|byte| [HEX_DIGITS[(byte >> 4) as usize], HEX_DIGITS[(byte & 0xf) as usize]])
+        .collect()
+}
+
+fn hex_value(digit: u8) -> Result<u8, EncodingError> {
+    match digit {
+        b'0'..=b'9' => Ok(digit - b'0'),
+        b'a'..=b'f' => Ok(digit - b'a' + 10),
+        b'A'..=b'F' => Ok(digit - b'A' + 10),
+        _ => Err(EncodingError::InvalidHexDigit(digit as char))
+    }
+}
+
+pub fn decode_hex(data: &[u8]) -> Result<Vec<u8>, EncodingError> {
+    if !data.len().is_multiple_of(2) {
+        return Err(EncodingError::OddHexLength);
+    }
+    data.chunks(2)
+        .map(|pair| Ok(hex_value(pair[0])? << 4 | hex_value(pair[1])?))
+        .collect()
+}
+
+pub fn encode_base64(data: &[u8]) -> Vec<u8> {
+    let mut res = Vec::with_capacity(data.len().div_ceil(3) * 4);
+    for group in data.chunks(3) {
+        let bits = (group[0] as u32) << 16
+            | (*group.get(1).unwrap_or(&0) as u32) << 8
+            | *group.get(2).unwrap_or(&0) as u32;
+
+        for idx in 0..4 {
+            if idx <= group.len() {
+                res.push(BASE64_ALPHABET[(bits >> (18 - 6 * idx) & 0x3f) as usize]);
+            }
+            else {
+                res.push(b'=');
+            }
+        }
+    }
+    res
+}
+
+fn base64_value(c: u8) -> Result<u32, EncodingError> {
+    BASE64_ALPHABET.iter()
+        .position(|letter| *letter == c)
+        .map(|idx| idx as u32)
+        .ok_or(EncodingError::InvalidBase64Char(c as char))
+}
+
+pub fn decode_base64(data: &[u8]) -> Result<Vec<u8>, EncodingError> {
+    if !data.len().is_multiple_of(4) {
+        return Err(EncodingError::InvalidBase64Length);
+    }
+
+    let mut res = Vec::with_capacity(data.len() / 4 * 3);
+    let groups = data.len() / 4;
+    for (group_idx, group) in data.chunks(4).enumerate() {
+        let padding = group.iter().rev().take_while(|c| **c == b'=').count();
+        if padding > 2 || (padding > 0 && group_idx + 1 != groups) {
+            return Err(EncodingError::InvalidBase64Padding);
+        }
+
+        let mut bits = 0;
+        for c in &group[..4 - padding] {
+            bits = bits << 6 | base64_value(*c)?;
+        }
+        bits <<= 6 * padding;
+
+        let bytes = [(bits >> 16) as u8, (bits >> 8) as u8, bits as u8];
+        res.extend_from_slice(&bytes[..3 - padding]);
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSAGES: [&str; 5] = ["", "f", "fo", "foo", "Grüße, 世界! 🦀"];
+
+    #[test]
+    fn test_base64_known_values() {
+        assert_eq!(encode_base64(b""), b"");
+        assert_eq!(encode_base64(b"f"), b"Zg==");
+        assert_eq!(encode_base64(b"fo"), b"Zm8=");
+        assert_eq!(encode_base64(b"foo"), b"Zm9v");
+        assert_eq!(encode_base64(b"foobar"), b"Zm9vYmFy");
+        assert_eq!(decode_base64(b"Zm9vYg==").unwrap(), b"foob");
+    }
+
+    #[test]
+    fn test_hex_known_values() {
+        assert_eq!(encode_hex(b"Hello"), b"48656c6c6f");
+        assert_eq!(decode_hex(b"48656C6c6F").unwrap(), b"Hello");
+    }
+
+    #[test]
+    fn test_round_trip_all_encodings() {
+        for encoding in Encoding::value_variants() {
+            for message in MESSAGES {
+                let encoded = encode(message.as_bytes(), *encoding);
+                assert_eq!(decode(&encoded, *encoding).unwrap(), message.as_bytes());
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip_binary() {
+        let data: Vec<u8> = (0..=255).collect();
+        assert_eq!(decode_base64(&encode_base64(&data)).unwrap(), data);
+        assert_eq!(decode_hex(&encode_hex(&data)).unwrap(), data);
+    }
+
+    #[test]
+    fn test_invalid_hex() {
+        assert_eq!(decode_hex(b"abc"), Err(EncodingError::OddHexLength));
+        assert_eq!(decode_hex(b"zz"), Err(EncodingError::InvalidHexDigit('z')));
+    }
+
+    #[test]
+    fn test_invalid_base64() {
+        assert_eq!(decode_base64(b"Zm9"), Err(EncodingError::InvalidBase64Length));
+        assert_eq!(decode_base64(b"Zm9!"), Err(EncodingError::InvalidBase64Char('!')));
+        assert_eq!(decode_base64(b"Z==="), Err(EncodingError::InvalidBase64Padding));
+        assert_eq!(decode_base64(b"Zg==Zg=="), Err(EncodingError::InvalidBase64Padding));
+    }
+}
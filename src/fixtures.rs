@@ -0,0 +1,131 @@
+use std::str::FromStr;
+
+use clap::ValueEnum;
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png, zlib};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorType {
+    Grayscale,
+    Rgb,
+    Indexed,
+    GrayscaleAlpha,
+    Rgba,
+}
+
+impl ColorType {
+
+    pub fn code(&self) -> u8 {
+        match self {
+            ColorType::Grayscale => 0,
+            ColorType::Rgb => 2,
+            ColorType::Indexed => 3,
+            ColorType::GrayscaleAlpha => 4,
+            ColorType::Rgba => 6,
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        match self {
+            ColorType::Grayscale | ColorType::Indexed => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+}
+
+fn chunk(chunk_type: &str, data: Vec<u8>) -> Chunk {
+    Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+}
+
+/// The chunks of a minimal valid 8-bit image of the given size: `IHDR`, `PLTE` for indexed
+/// images, a single all-zero `IDAT` and `IEND`.
+pub fn minimal_chunks(width: u32, height: u32, color_type: ColorType) -> Vec<Chunk> {
+    let mut ihdr: Vec<u8> = width.to_be_bytes().iter()
+        .chain(height.to_be_bytes().iter())
+        .copied()
+        .collect();
+    ihdr.extend([8, color_type.code(), 0, 0, 0]);
+
+    // every scanline is a filter-type byte followed by the pixel bytes
+    let scanline = 1 + width as usize * color_type.channels();
+    let raw = vec!(0; scanline * height as usize);
+
+    let mut chunks = vec!(chunk("IHDR", ihdr));
+    if color_type == ColorType::Indexed {
+        chunks.push(chunk("PLTE", vec!(0, 0, 0)));
+    }
+    chunks.push(chunk("IDAT", zlib::compress_stored(&raw)));
+    chunks.push(chunk("IEND", vec!()));
+    chunks
+}
+
+/// Serializes `chunks` after the PNG signature.
+pub fn png_bytes(chunks: &[Chunk]) -> Vec<u8> {
+    Png::STANDARD_HEADER.iter()
+        .copied()
+        .chain(chunks.iter().flat_map(|chunk| chunk.as_bytes()))
+        .collect()
+}
+
+/// A minimal valid png of the given size.
+pub fn minimal_png(width: u32, height: u32, color_type: ColorType) -> Png {
+    with_chunks(width, height, color_type, &[])
+}
+
+/// A minimal valid png with `extra` chunks embedded just before `IEND`.
+pub fn with_chunks(width: u32, height: u32, color_type: ColorType, extra: &[(&str, &[u8])]) -> Png {
+    let mut chunks = minimal_chunks(width, height, color_type);
+    let iend = chunks.pop().unwrap();
+    chunks.extend(extra.iter().map(|(chunk_type, data)| chunk(chunk_type, data.to_vec())));
+    chunks.push(iend);
+    Png::try_from(png_bytes(&chunks).as_slice()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dimensions(png: &Png) -> (u32, u32) {
+        let ihdr = png.chunk_by_type("IHDR").unwrap().data();
+        (
+            u32::from_be_bytes(ihdr[0..4].try_into().unwrap()),
+            u32::from_be_bytes(ihdr[4..8].try_into().unwrap()),
+        )
+    }
+
+    #[test]
+    fn test_minimal_png_dimensions() {
+        for (width, height) in [(1, 1), (2, 2), (16, 3), (300, 1)] {
+            let png = minimal_png(width, height, ColorType::Rgba);
+            assert_eq!(dimensions(&png), (width, height));
+        }
+    }
+
+    #[test]
+    fn test_minimal_png_color_types() {
+        for color_type in ColorType::value_variants() {
+            let png = minimal_png(2, 2, *color_type);
+            let ihdr = png.chunk_by_type("IHDR").unwrap();
+            assert_eq!(ihdr.length(), 13);
+            assert_eq!(ihdr.data()[9], color_type.code());
+            assert_eq!(png.chunk_by_type("PLTE").is_some(), *color_type == ColorType::Indexed);
+        }
+    }
+
+    #[test]
+    fn test_minimal_png_round_trips() {
+        let png = minimal_png(4, 4, ColorType::Rgb);
+        let bytes = png.as_bytes();
+        assert_eq!(Png::try_from(bytes.as_slice()).unwrap().as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_with_chunks() {
+        let png = with_chunks(2, 2, ColorType::Rgb, &[("ruSt", b"hidden")]);
+        let types: Vec<String> = png.chunks().iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "IDAT", "ruSt", "IEND"]);
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), b"hidden");
+    }
+}
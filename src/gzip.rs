@@ -0,0 +1,117 @@
+use crc::{Crc, CRC_32_ISO_HDLC};
+use thiserror::Error;
+
+use crate::zlib::{self, ZlibError};
+
+/// The two bytes every gzip member starts with.
+pub const MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+const CRC: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+const FLAG_HCRC: u8 = 0x02;
+const FLAG_EXTRA: u8 = 0x04;
+const FLAG_NAME: u8 = 0x08;
+const FLAG_COMMENT: u8 = 0x10;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum GzipError {
+    #[error("Invalid gzip header")]
+    InvalidHeader,
+    #[error("Gzip data ends unexpectedly")]
+    UnexpectedEnd,
+    #[error("Invalid deflate data: {0}")]
+    Deflate(#[from] ZlibError),
+    #[error("CRC-32 checksum does not match")]
+    ChecksumMismatch,
+    #[error("Decompressed length does not match the gzip trailer")]
+    LengthMismatch,
+}
+
+pub fn is_gzip(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+/// Compresses `data` into a single gzip member with no file name or timestamp.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut res = vec!(MAGIC[0], MAGIC[1], 8, 0, 0, 0, 0, 0, 0, 0xff);
+    res.extend(zlib::deflate(data));
+    res.extend(CRC.checksum(data).to_le_bytes());
+    res.extend((data.len() as u32).to_le_bytes());
+    res
+}
+
+/// Decompresses the first gzip member in `data`, checking its CRC-32 and length.
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, GzipError> {
+    let header = data.get(..10).ok_or(GzipError::UnexpectedEnd)?;
+    if !is_gzip(header) || header[2] != 8 {
+        return Err(GzipError::InvalidHeader);
+    }
+    let flags = header[3];
+
+    let mut pos = 10;
+    if flags & FLAG_EXTRA != 0 {
+        let len = data.get(pos..pos + 2).ok_or(GzipError::UnexpectedEnd)?;
+        pos += 2 + u16::from_le_bytes([len[0], len[1]]) as usize;
+    }
+    for flag in [FLAG_NAME, FLAG_COMMENT] {
+        if flags & flag != 0 {
+            let rest = data.get(pos..).ok_or(GzipError::UnexpectedEnd)?;
+            pos += rest.iter().position(|byte| *byte == 0).ok_or(GzipError::UnexpectedEnd)? + 1;
+        }
+    }
+    if flags & FLAG_HCRC != 0 {
+        pos += 2;
+    }
+
+    let (res, consumed) = zlib::inflate_with_len(data.get(pos..).ok_or(GzipError::UnexpectedEnd)?)?;
+    let trailer = data.get(pos + consumed..pos + consumed + 8).ok_or(GzipError::UnexpectedEnd)?;
+    if u32::from_le_bytes(trailer[..4].try_into().unwrap()) != CRC.checksum(&res) {
+        return Err(GzipError::ChecksumMismatch);
+    }
+    if u32::from_le_bytes(trailer[4..].try_into().unwrap()) != res.len() as u32 {
+        return Err(GzipError::LengthMismatch);
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let data = b"gzip me, gzip me, gzip me please".repeat(20);
+        let compressed = compress(&data);
+        assert!(is_gzip(&compressed));
+        assert!(compressed.len() < data.len());
+        assert_eq!(decompress(&compressed).unwrap(), data);
+        assert_eq!(decompress(&compress(&[])).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_decompress_with_file_name() {
+        // a member that records the original file name, as plain `gzip` does
+        let mut data = vec!(0x1f, 0x8b, 8, FLAG_NAME, 0, 0, 0, 0, 0, 3, b'h', 0);
+        data.extend(zlib::deflate(b"hello"));
+        data.extend(CRC.checksum(b"hello").to_le_bytes());
+        data.extend(5u32.to_le_bytes());
+        assert_eq!(decompress(&data).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_decompress_errors() {
+        let mut data = compress(b"hello");
+        assert_eq!(decompress(&data[..5]), Err(GzipError::UnexpectedEnd));
+        assert_eq!(decompress(&data[..data.len() - 2]), Err(GzipError::UnexpectedEnd));
+
+        let len = data.len();
+        data[len - 1] ^= 1;
+        assert_eq!(decompress(&data), Err(GzipError::LengthMismatch));
+        data[len - 8] ^= 1;
+        assert_eq!(decompress(&data), Err(GzipError::ChecksumMismatch));
+
+        data[2] = 7;
+        assert_eq!(decompress(&data), Err(GzipError::InvalidHeader));
+    }
+}
@@ -0,0 +1,100 @@
+use std::fmt::Write;
+
+pub const DEFAULT_WIDTH: usize = 16;
+pub const DEFAULT_MAX_BYTES: usize = 256;
+
+/// Renders `data` as a classic hexdump: offset, `width` hex bytes per row and an ASCII gutter.
+pub fn hexdump(data: &[u8], width: usize) -> String {
+    let mut res = String::new();
+
+    for (row, bytes) in data.chunks(width).enumerate() {
+        let _ = write!(res, "{:08x}  ", row * width);
+
+        for idx in 0..width {
+            match bytes.get(idx) {
+                Some(byte) => { let _ = write!(res, "{:02x} ", byte); },
+                None => res.push_str("   ")
+            }
+        }
+
+        res.push_str(" |");
+        for byte in bytes {
+            if byte.is_ascii_graphic() || *byte == b' ' {
+                res.push(*byte as char);
+            }
+            else {
+                res.push('.');
+            }
+        }
+        res.push_str("|\n");
+    }
+
+    res
+}
+
+/// Like [`hexdump`], but only dumps the first `max_bytes` bytes and notes how many were left out.
+pub fn hexdump_truncated(data: &[u8], width: usize, max_bytes: usize) -> String {
+    if data.len() <= max_bytes {
+        return hexdump(data, width);
+    }
+
+    let mut res = hexdump(&data[..max_bytes], width);
+    let _ = writeln!(res, "... {} more bytes", data.len() - max_bytes);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hexdump_default_width() {
+        let data = "RuSt".as_bytes();
+        let expected = format!("00000000  52 75 53 74 {}|RuSt|\n", " ".repeat(12 * 3 + 1));
+        assert_eq!(hexdump(data, DEFAULT_WIDTH), expected);
+    }
+
+    #[test]
+    fn test_hexdump_width_8() {
+        let data: Vec<u8> = (0x41..0x53).collect();
+        let expected = concat!(
+            "00000000  41 42 43 44 45 46 47 48  |ABCDEFGH|\n",
+            "00000008  49 4a 4b 4c 4d 4e 4f 50  |IJKLMNOP|\n",
+            "00000010  51 52                    |QR|\n",
+        );
+        assert_eq!(hexdump(&data, 8), expected);
+    }
+
+    #[test]
+    fn test_hexdump_20_bytes() {
+        let data = "This is 20 bytes!!\x00\x7f".as_bytes();
+        let expected = concat!(
+            "00000000  54 68 69 73 20 69 73 20 32 30 20 62 79 74 65 73  |This is 20 bytes|\n",
+            "00000010  21 21 00 7f                                      |!!..|\n",
+        );
+        assert_eq!(hexdump(data, DEFAULT_WIDTH), expected);
+    }
+
+    #[test]
+    fn test_hexdump_truncated() {
+        let data: Vec<u8> = (0x41..0x55).collect();
+        let expected = concat!(
+            "00000000  41 42 43 44 45 46 47 48  |ABCDEFGH|\n",
+            "... 12 more bytes\n",
+        );
+        assert_eq!(hexdump_truncated(&data, 8, 8), expected);
+        assert_eq!(hexdump_truncated(&data, 8, 20), hexdump(&data, 8));
+    }
+
+    #[test]
+    fn test_hexdump_non_printable() {
+        let data = [0u8, 10, 65, 255];
+        let expected = "00000000  00 0a 41 ff  |..A.|\n";
+        assert_eq!(hexdump(&data, 4), expected);
+    }
+
+    #[test]
+    fn test_hexdump_empty() {
+        assert_eq!(hexdump(&[], DEFAULT_WIDTH), "");
+    }
+}
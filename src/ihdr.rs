@@ -0,0 +1,145 @@
+use std::fmt::Display;
+
+use thiserror::Error;
+
+use crate::{chunk::Chunk, png::Png};
+
+/// The image properties stored in the 13 data bytes of an `IHDR` chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageHeader {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: u8,
+    pub compression_method: u8,
+    pub filter_method: u8,
+    pub interlace_method: u8,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum IhdrError {
+    #[error("IHDR data must be exactly 13 bytes, found {0}")]
+    InvalidLength(usize),
+    #[error("The first chunk is not IHDR")]
+    NotFirst,
+    #[error("Chunk is not an IHDR chunk")]
+    NotIhdr,
+}
+
+impl ImageHeader {
+    /// The length of the data of every `IHDR` chunk.
+    pub const LENGTH: usize = 13;
+
+    /// Reads the big-endian width and height followed by the five single-byte fields.
+    pub fn from_bytes(data: &[u8]) -> Result<ImageHeader, IhdrError> {
+        if data.len() != Self::LENGTH {
+            return Err(IhdrError::InvalidLength(data.len()));
+        }
+        Ok(ImageHeader {
+            width: u32::from_be_bytes(data[0..4].try_into().unwrap()),
+            height: u32::from_be_bytes(data[4..8].try_into().unwrap()),
+            bit_depth: data[8],
+            color_type: data[9],
+            compression_method: data[10],
+            filter_method: data[11],
+            interlace_method: data[12],
+        })
+    }
+
+    /// The header of `png`, which must be its first chunk.
+    pub fn from_png(png: &Png) -> Result<ImageHeader, IhdrError> {
+        match png.chunks().first() {
+            Some(chunk) if chunk.type_str() == "IHDR" => ImageHeader::try_from(chunk),
+            _ => Err(IhdrError::NotFirst)
+        }
+    }
+
+    /// The name of the color type, or `None` for codes the spec doesn't define.
+    pub fn color_type_name(&self) -> Option<&'static str> {
+        match self.color_type {
+            0 => Some("grayscale"),
+            2 => Some("RGB"),
+            3 => Some("indexed"),
+            4 => Some("grayscale with alpha"),
+            6 => Some("RGBA"),
+            _ => None
+        }
+    }
+
+    /// The name of the interlace method, or `None` for codes the spec doesn't define.
+    pub fn interlace_name(&self) -> Option<&'static str> {
+        match self.interlace_method {
+            0 => Some("none"),
+            1 => Some("Adam7"),
+            _ => None
+        }
+    }
+}
+
+impl TryFrom<&Chunk> for ImageHeader {
+    type Error = IhdrError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        if chunk.type_str() != "IHDR" {
+            return Err(IhdrError::NotIhdr);
+        }
+        ImageHeader::from_bytes(chunk.data())
+    }
+}
+
+impl Display for ImageHeader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Dimensions: {}x{}", self.width, self.height)?;
+        writeln!(f, "Bit depth: {}", self.bit_depth)?;
+        writeln!(f, "Color type: {} ({})", self.color_type_name().unwrap_or("unknown"), self.color_type)?;
+        write!(f, "Interlace: {} ({})", self.interlace_name().unwrap_or("unknown"), self.interlace_method)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+
+    // 640x480, 8-bit RGBA, Adam7 interlaced
+    const IHDR: [u8; 13] = [0, 0, 2, 128, 0, 0, 1, 224, 8, 6, 0, 0, 1];
+
+    #[test]
+    fn test_parse_known_layout() {
+        let header = ImageHeader::from_bytes(&IHDR).unwrap();
+        assert_eq!(header, ImageHeader {
+            width: 640,
+            height: 480,
+            bit_depth: 8,
+            color_type: 6,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: 1,
+        });
+        assert_eq!(header.to_string(), "Dimensions: 640x480\nBit depth: 8\nColor type: RGBA (6)\nInterlace: Adam7 (1)");
+    }
+
+    #[test]
+    fn test_from_png() {
+        let chunk = |chunk_type: &str, data: &[u8]| Chunk::new(ChunkType::parse(chunk_type).unwrap(), data.to_vec());
+        let png = Png::from_chunks(vec![chunk("IHDR", &IHDR), chunk("IDAT", &[1]), chunk("IEND", &[])]);
+        assert_eq!(ImageHeader::from_png(&png).unwrap().width, 640);
+
+        let short = Png::from_chunks(vec![chunk("IHDR", &IHDR[..12]), chunk("IEND", &[])]);
+        assert_eq!(ImageHeader::from_png(&short), Err(IhdrError::InvalidLength(12)));
+        let misplaced = Png::from_chunks(vec![chunk("ruSt", &[]), chunk("IHDR", &IHDR)]);
+        assert_eq!(ImageHeader::from_png(&misplaced), Err(IhdrError::NotFirst));
+        assert_eq!(ImageHeader::try_from(&chunk("IDAT", &IHDR)), Err(IhdrError::NotIhdr));
+    }
+
+    #[test]
+    fn test_unknown_codes() {
+        let mut data = IHDR;
+        data[9] = 5;
+        data[12] = 7;
+        let header = ImageHeader::from_bytes(&data).unwrap();
+        assert_eq!(header.color_type_name(), None);
+        assert!(header.to_string().ends_with("Color type: unknown (5)\nInterlace: unknown (7)"));
+    }
+}
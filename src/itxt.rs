@@ -0,0 +1,206 @@
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, zlib::{self, ZlibError}};
+
+/// The contents of an `iTXt` chunk: a Latin-1 keyword plus UTF-8 text, optionally zlib-compressed,
+/// with a language tag and the keyword translated into that language.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InternationalText {
+    pub keyword: String,
+    pub compressed: bool,
+    pub language: String,
+    pub translated_keyword: String,
+    pub text: String,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ITxtError {
+    #[error("Keyword must be 1-79 printable Latin-1 characters")]
+    InvalidKeyword,
+    #[error("Missing null separator after the {0}")]
+    MissingSeparator(&'static str),
+    #[error("Missing compression flag and method")]
+    MissingCompressionFields,
+    #[error("Unknown compression flag {0}")]
+    InvalidCompressionFlag(u8),
+    #[error("Unknown compression method {0}")]
+    UnknownCompressionMethod(u8),
+    #[error("The {0} is not valid UTF-8")]
+    InvalidUtf8(&'static str),
+    #[error("Invalid compressed text: {0}")]
+    Zlib(#[from] ZlibError),
+    #[error("Chunk is not an iTXt chunk")]
+    NotITxt,
+}
+
+/// Keywords shared by the textual chunks: 1-79 printable Latin-1 characters with no leading,
+/// trailing or doubled spaces.
+pub(crate) fn is_valid_keyword(keyword: &str) -> bool {
+    (1..=79).contains(&keyword.chars().count())
+        && keyword.chars().all(|c| matches!(c as u32, 0x20..=0x7e | 0xa1..=0xff))
+        && !keyword.starts_with(' ')
+        && !keyword.ends_with(' ')
+        && !keyword.contains("  ")
+}
+
+/// Splits off everything up to the next null byte, returning it and the rest after the null.
+pub(crate) fn split_null<'a>(data: &'a [u8], field: &'static str) -> Result<(&'a [u8], &'a [u8]), ITxtError> {
+    let idx = data.iter().position(|byte| *byte == 0).ok_or(ITxtError::MissingSeparator(field))?;
+    Ok((&data[..idx], &data[idx + 1..]))
+}
+
+/// Splits off and validates the Latin-1 keyword every textual chunk starts with.
+pub(crate) fn split_keyword(data: &[u8]) -> Result<(String, &[u8]), ITxtError> {
+    let (keyword, rest) = split_null(data, "keyword")?;
+    let keyword: String = keyword.iter().map(|byte| *byte as char).collect();
+    if !is_valid_keyword(&keyword) {
+        return Err(ITxtError::InvalidKeyword);
+    }
+    Ok((keyword, rest))
+}
+
+fn utf8(data: &[u8], field: &'static str) -> Result<String, ITxtError> {
+    String::from_utf8(data.to_vec()).map_err(|_| ITxtError::InvalidUtf8(field))
+}
+
+impl InternationalText {
+    /// Uncompressed text with no language tag.
+    pub fn new(keyword: &str, text: &str) -> InternationalText {
+        InternationalText {
+            keyword: keyword.to_string(),
+            compressed: false,
+            language: String::new(),
+            translated_keyword: String::new(),
+            text: text.to_string(),
+        }
+    }
+
+    pub fn chunk_type() -> ChunkType {
+        ChunkType::from_str("iTXt").unwrap()
+    }
+
+    /// Lays the fields out as `keyword\0 flag method language\0 translated\0 text`.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ITxtError> {
+        if !is_valid_keyword(&self.keyword) {
+            return Err(ITxtError::InvalidKeyword);
+        }
+
+        // the keyword is Latin-1, so each char is exactly one byte
+        let mut res: Vec<u8> = self.keyword.chars().map(|c| c as u8).collect();
+        res.push(0);
+        res.push(self.compressed as u8);
+        res.push(0);
+        res.extend(self.language.as_bytes());
+        res.push(0);
+        res.extend(self.translated_keyword.as_bytes());
+        res.push(0);
+        if self.compressed {
+            res.extend(zlib::compress(self.text.as_bytes()));
+        }
+        else {
+            res.extend(self.text.as_bytes());
+        }
+        Ok(res)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<InternationalText, ITxtError> {
+        let (keyword, rest) = split_keyword(data)?;
+
+        let (flag, method, rest) = match rest {
+            [flag, method, rest @ ..] => (*flag, *method, rest),
+            _ => return Err(ITxtError::MissingCompressionFields)
+        };
+        let compressed = match flag {
+            0 => false,
+            1 => true,
+            _ => return Err(ITxtError::InvalidCompressionFlag(flag))
+        };
+        if compressed && method != 0 {
+            return Err(ITxtError::UnknownCompressionMethod(method));
+        }
+
+        let (language, rest) = split_null(rest, "language tag")?;
+        let (translated_keyword, text) = split_null(rest, "translated keyword")?;
+        let text = if compressed { zlib::decompress(text)? } else { text.to_vec() };
+
+        Ok(InternationalText {
+            keyword,
+            compressed,
+            language: utf8(language, "language tag")?,
+            translated_keyword: utf8(translated_keyword, "translated keyword")?,
+            text: utf8(&text, "text")?,
+        })
+    }
+
+    pub fn to_chunk(&self) -> Result<Chunk, ITxtError> {
+        Ok(Chunk::new(Self::chunk_type(), self.to_bytes()?))
+    }
+}
+
+impl TryFrom<&Chunk> for InternationalText {
+    type Error = ITxtError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        if *chunk.chunk_type() != Self::chunk_type() {
+            return Err(ITxtError::NotITxt);
+        }
+        InternationalText::from_bytes(chunk.data())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hand_built_layout() {
+        let mut data = b"Title\0\0\0ja\0".to_vec();
+        data.extend("タイトル".as_bytes());
+        data.push(0);
+        data.extend("サイコロ 🎲".as_bytes());
+
+        let text = InternationalText::from_bytes(&data).unwrap();
+        assert_eq!(text, InternationalText {
+            keyword: String::from("Title"),
+            compressed: false,
+            language: String::from("ja"),
+            translated_keyword: String::from("タイトル"),
+            text: String::from("サイコロ 🎲"),
+        });
+        assert_eq!(text.to_bytes().unwrap(), data);
+    }
+
+    #[test]
+    fn test_compressed_round_trip() {
+        let text = InternationalText { compressed: true, ..InternationalText::new("Description", &"Grüße ".repeat(40)) };
+        let bytes = text.to_bytes().unwrap();
+        assert!(bytes.len() < text.text.len());
+        assert_eq!(&bytes[..14], b"Description\0\x01\x00");
+        assert_eq!(InternationalText::from_bytes(&bytes).unwrap(), text);
+    }
+
+    #[test]
+    fn test_chunk_round_trip() {
+        let text = InternationalText::new("Author", "Ærøskøbing");
+        let chunk = text.to_chunk().unwrap();
+        assert_eq!(chunk.chunk_type().to_string(), "iTXt");
+        assert_eq!(InternationalText::try_from(&chunk).unwrap(), text);
+    }
+
+    #[test]
+    fn test_invalid_layouts() {
+        assert_eq!(InternationalText::from_bytes(b"Title"), Err(ITxtError::MissingSeparator("keyword")));
+        assert_eq!(InternationalText::from_bytes(b"\0\0\0\0\0"), Err(ITxtError::InvalidKeyword));
+        assert_eq!(InternationalText::from_bytes(b"Title\0"), Err(ITxtError::MissingCompressionFields));
+        assert_eq!(InternationalText::from_bytes(b"Title\0\x02\0\0\0"), Err(ITxtError::InvalidCompressionFlag(2)));
+        assert_eq!(InternationalText::from_bytes(b"Title\0\x01\x05\0\0"), Err(ITxtError::UnknownCompressionMethod(5)));
+        assert_eq!(InternationalText::from_bytes(b"Title\0\0\0en"), Err(ITxtError::MissingSeparator("language tag")));
+        assert_eq!(InternationalText::from_bytes(b"Title\0\0\0\0\0\xff"), Err(ITxtError::InvalidUtf8("text")));
+        assert!(matches!(InternationalText::from_bytes(b"Title\0\x01\0\0\0junk"), Err(ITxtError::Zlib(_))));
+        assert_eq!(InternationalText::new(" padded", "").to_bytes(), Err(ITxtError::InvalidKeyword));
+        assert_eq!(InternationalText::new("smile 🙂", "").to_bytes(), Err(ITxtError::InvalidKeyword));
+    }
+}
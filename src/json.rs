@@ -0,0 +1,426 @@
+use std::fmt::{Display, Write};
+
+use thiserror::Error;
+
+/// A parsed JSON value. Objects keep their keys in document order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum JsonError {
+    #[error("Unexpected end of input")]
+    UnexpectedEnd,
+    #[error("Unexpected character {0:?} at byte {1}")]
+    UnexpectedChar(char, usize),
+    #[error("Invalid number at byte {0}")]
+    InvalidNumber(usize),
+    #[error("Invalid escape sequence at byte {0}")]
+    InvalidEscape(usize),
+    #[error("Trailing characters after the value at byte {0}")]
+    TrailingCharacters(usize),
+}
+
+impl Json {
+    /// Looks up `key` in an object, returning `None` for missing keys and non-objects.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(entries) => entries.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(value) => Some(value),
+            _ => None
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(values) => Some(values),
+            _ => None
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(value) => Some(*value),
+            _ => None
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(value) => Some(*value),
+            _ => None
+        }
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Json::String(value.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Json::String(value)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Self {
+        Json::Bool(value)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Self {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<u32> for Json {
+    fn from(value: u32) -> Self {
+        Json::Number(value as f64)
+    }
+}
+
+/// Parses a complete JSON document.
+pub fn parse(input: &str) -> Result<Json, JsonError> {
+    let mut parser = Parser { bytes: input.as_bytes(), pos: 0 };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos != parser.bytes.len() {
+        return Err(JsonError::TrailingCharacters(parser.pos));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.bytes.get(self.pos).is_some_and(u8::is_ascii_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Result<u8, JsonError> {
+        self.skip_whitespace();
+        self.bytes.get(self.pos).copied().ok_or(JsonError::UnexpectedEnd)
+    }
+
+    fn unexpected(&self) -> JsonError {
+        match std::str::from_utf8(&self.bytes[self.pos..]).ok().and_then(|rest| rest.chars().next()) {
+            Some(c) => JsonError::UnexpectedChar(c, self.pos),
+            None => JsonError::UnexpectedEnd
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), JsonError> {
+        if self.peek()? != byte {
+            return Err(self.unexpected());
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, JsonError> {
+        if !self.bytes[self.pos..].starts_with(word.as_bytes()) {
+            return Err(self.unexpected());
+        }
+        self.pos += word.len();
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Json, JsonError> {
+        match self.peek()? {
+            b'n' => self.literal("null", Json::Null),
+            b't' => self.literal("true", Json::Bool(true)),
+            b'f' => self.literal("false", Json::Bool(false)),
+            b'"' => Ok(Json::String(self.string()?)),
+            b'[' => self.array(),
+            b'{' => self.object(),
+            b'-' | b'0'..=b'9' => self.number(),
+            _ => Err(self.unexpected())
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, JsonError> {
+        self.expect(b'[')?;
+        let mut values = vec!();
+        if self.peek()? == b']' {
+            self.pos += 1;
+            return Ok(Json::Array(values));
+        }
+        loop {
+            values.push(self.value()?);
+            match self.peek()? {
+                b',' => self.pos += 1,
+                b']' => { self.pos += 1; return Ok(Json::Array(values)); },
+                _ => return Err(self.unexpected())
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, JsonError> {
+        self.expect(b'{')?;
+        let mut entries = vec!();
+        if self.peek()? == b'}' {
+            self.pos += 1;
+            return Ok(Json::Object(entries));
+        }
+        loop {
+            if self.peek()? != b'"' {
+                return Err(self.unexpected());
+            }
+            let key = self.string()?;
+            self.expect(b':')?;
+            entries.push((key, self.value()?));
+            match self.peek()? {
+                b',' => self.pos += 1,
+                b'}' => { self.pos += 1; return Ok(Json::Object(entries)); },
+                _ => return Err(self.unexpected())
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, JsonError> {
+        let start = self.pos;
+        while self.bytes.get(self.pos).is_some_and(|c| matches!(c, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')) {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.pos]).ok()
+            .and_then(|number| number.parse().ok())
+            .map(Json::Number)
+            .ok_or(JsonError::InvalidNumber(start))
+    }
+
+    fn hex4(&mut self) -> Result<u32, JsonError> {
+        let digits = self.bytes.get(self.pos..self.pos + 4).ok_or(JsonError::UnexpectedEnd)?;
+        let value = std::str::from_utf8(digits).ok()
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or(JsonError::InvalidEscape(self.pos))?;
+        self.pos += 4;
+        Ok(value)
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.expect(b'"')?;
+        let mut bytes = vec!();
+        loop {
+            let byte = *self.bytes.get(self.pos).ok_or(JsonError::UnexpectedEnd)?;
+            self.pos += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let escape = *self.bytes.get(self.pos).ok_or(JsonError::UnexpectedEnd)?;
+                    self.pos += 1;
+                    let c = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let start = self.pos - 2;
+                            let mut code = self.hex4()?;
+                            if (0xd800..0xdc00).contains(&code) && self.bytes[self.pos..].starts_with(b"\\u") {
+                                self.pos += 2;
+                                let low = self.hex4()?;
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff);
+                            }
+                            char::from_u32(code).ok_or(JsonError::InvalidEscape(start))?
+                        },
+                        _ => return Err(JsonError::InvalidEscape(self.pos - 2))
+                    };
+                    bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                },
+                _ => bytes.push(byte)
+            }
+        }
+        // the input was a &str and escapes produce whole chars, so this is always valid UTF-8
+        Ok(String::from_utf8(bytes).unwrap())
+    }
+}
+
+fn write_string(f: &mut std::fmt::Formatter<'_>, value: &str) -> std::fmt::Result {
+    f.write_char('"')?;
+    for c in value.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+impl Json {
+    /// Indented JSON with two spaces per level, one array item or object entry per line.
+    pub fn to_string_pretty(&self) -> String {
+        let mut res = String::new();
+        self.write_pretty(&mut res, 0);
+        res
+    }
+
+    fn write_pretty(&self, res: &mut String, depth: usize) {
+        let indent = |res: &mut String, depth: usize| res.push_str(&"  ".repeat(depth));
+        match self {
+            Json::Array(values) if !values.is_empty() => {
+                res.push_str("[\n");
+                for (idx, value) in values.iter().enumerate() {
+                    if idx > 0 {
+                        res.push_str(",\n");
+                    }
+                    indent(res, depth + 1);
+                    value.write_pretty(res, depth + 1);
+                }
+                res.push('\n');
+                indent(res, depth);
+                res.push(']');
+            },
+            Json::Object(entries) if !entries.is_empty() => {
+                res.push_str("{\n");
+                for (idx, (key, value)) in entries.iter().enumerate() {
+                    if idx > 0 {
+                        res.push_str(",\n");
+                    }
+                    indent(res, depth + 1);
+                    res.push_str(&format!("{}: ", Json::from(key.as_str())));
+                    value.write_pretty(res, depth + 1);
+                }
+                res.push('\n');
+                indent(res, depth);
+                res.push('}');
+            },
+            // scalars and empty containers look the same either way
+            _ => res.push_str(&self.to_string()),
+        }
+    }
+}
+
+/// Writes compact JSON.
+impl Display for Json {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(value) if value.is_finite() => write!(f, "{}", value),
+            Json::Number(_) => f.write_str("null"),
+            Json::String(value) => write_string(f, value),
+            Json::Array(values) => {
+                f.write_char('[')?;
+                for (idx, value) in values.iter().enumerate() {
+                    if idx > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                f.write_char(']')
+            },
+            Json::Object(entries) => {
+                f.write_char('{')?;
+                for (idx, (key, value)) in entries.iter().enumerate() {
+                    if idx > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_char('}')
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_values() {
+        assert_eq!(parse("null").unwrap(), Json::Null);
+        assert_eq!(parse(" true ").unwrap(), Json::Bool(true));
+        assert_eq!(parse("-12.5e1").unwrap(), Json::Number(-125.0));
+        assert_eq!(parse(r#""a\"b\\c\n\u00e9\ud83e\udd80""#).unwrap(), Json::from("a\"b\\c\né🦀"));
+        assert_eq!(parse("[1, [], {}]").unwrap(), Json::Array(vec![Json::Number(1.0), Json::Array(vec![]), Json::Object(vec![])]));
+    }
+
+    #[test]
+    fn test_parse_object() {
+        let value = parse(r#"{"name": "ruSt", "size": 3, "tags": ["a", "b"]}"#).unwrap();
+        assert_eq!(value.get("name").and_then(Json::as_str), Some("ruSt"));
+        assert_eq!(value.get("size").and_then(Json::as_f64), Some(3.0));
+        assert_eq!(value.get("tags").and_then(Json::as_array).map(|tags| tags.len()), Some(2));
+        assert_eq!(value.get("missing"), None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse(""), Err(JsonError::UnexpectedEnd));
+        assert_eq!(parse("[1,]"), Err(JsonError::UnexpectedChar(']', 3)));
+        assert_eq!(parse("{\"a\" 1}"), Err(JsonError::UnexpectedChar('1', 5)));
+        assert_eq!(parse("\"\\x\""), Err(JsonError::InvalidEscape(1)));
+        assert_eq!(parse("1 2"), Err(JsonError::TrailingCharacters(2)));
+        assert_eq!(parse("--1"), Err(JsonError::InvalidNumber(0)));
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        let value = Json::Object(vec![
+            (String::from("type"), Json::from("tEXt")),
+            (String::from("text"), Json::from("line\n\"quoted\"\u{1}")),
+            (String::from("length"), Json::from(12usize)),
+            (String::from("ok"), Json::from(true)),
+            (String::from("items"), Json::Array(vec![Json::Null, Json::Number(1.5)])),
+        ]);
+        let text = value.to_string();
+        assert_eq!(text, r#"{"type":"tEXt","text":"line\n\"quoted\"\u0001","length":12,"ok":true,"items":[null,1.5]}"#);
+        assert_eq!(parse(&text).unwrap(), value);
+    }
+
+    #[test]
+    fn test_pretty() {
+        let value = parse(r#"{"chunks":[{"type":"IHDR","length":13},{"type":"ruSt","tags":[]}],"ok":true}"#).unwrap();
+        let pretty = value.to_string_pretty();
+        assert_eq!(pretty, [
+            "{",
+            "  \"chunks\": [",
+            "    {",
+            "      \"type\": \"IHDR\",",
+            "      \"length\": 13",
+            "    },",
+            "    {",
+            "      \"type\": \"ruSt\",",
+            "      \"tags\": []",
+            "    }",
+            "  ],",
+            "  \"ok\": true",
+            "}",
+        ].join("\n"));
+        assert!(!value.to_string().contains('\n'));
+        assert_eq!(parse(&pretty).unwrap(), parse(&value.to_string()).unwrap());
+    }
+}
@@ -0,0 +1,19 @@
+pub mod chunk;
+pub mod chunk_type;
+pub mod diff;
+pub mod encoding;
+#[cfg(feature = "fixtures")]
+pub mod fixtures;
+pub mod gzip;
+pub mod hexdump;
+pub mod ihdr;
+pub mod itxt;
+pub mod json;
+pub mod logging;
+pub mod png;
+pub mod progress;
+pub mod sha256;
+pub mod split;
+pub mod verify;
+pub mod zlib;
+pub mod ztxt;
@@ -0,0 +1,57 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+pub const DEBUG: u8 = 1;
+pub const TRACE: u8 = 2;
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(0);
+
+/// Sets how chatty the `debug!`/`trace!` macros are; `0` keeps them silent.
+pub fn init(verbosity: u8) {
+    VERBOSITY.store(verbosity, Ordering::Relaxed);
+}
+
+pub fn enabled(level: u8) -> bool {
+    VERBOSITY.load(Ordering::Relaxed) >= level
+}
+
+/// Logs to stderr when running with `-v` or more.
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if $crate::logging::enabled($crate::logging::DEBUG) {
+            eprintln!("[debug] {}", format_args!($($arg)*));
+        }
+    };
+}
+
+/// Logs to stderr when running with `-vv` or more.
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        if $crate::logging::enabled($crate::logging::TRACE) {
+            eprintln!("[trace] {}", format_args!($($arg)*));
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels() {
+        init(0);
+        assert!(!enabled(DEBUG));
+        assert!(!enabled(TRACE));
+
+        init(1);
+        assert!(enabled(DEBUG));
+        assert!(!enabled(TRACE));
+
+        init(2);
+        assert!(enabled(DEBUG));
+        assert!(enabled(TRACE));
+
+        init(0);
+    }
+}
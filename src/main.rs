@@ -1,75 +1,2650 @@
-use cli::Cli;
+use cli::{Cli, DecodeAtOptions, DecodeOptions, EncodeOptions, InputOptions, ListFormat, ListOptions, OutputOptions, PrintOptions};
+use color::Palette;
 use clap::Parser;
-use png::Png;
+#[cfg(feature = "fixtures")]
+use pngme::fixtures;
+use pngme::{chunk::Chunk, chunk_type::ChunkType, debug, diff, encoding, gzip, hexdump, ihdr::ImageHeader, itxt::InternationalText, json::{self, Json}, logging, ztxt::CompressedText, png::{OnCollision, ParseOptions, Png, PngError}, progress::{self, ProgressWriter}, sha256, split, verify};
 
-use std::str::FromStr;
-
-use crate::{chunk_type::ChunkType, chunk::Chunk};
+use std::{cell::OnceCell, collections::BTreeMap, fs::FileTimes, io::{BufRead, BufWriter, IsTerminal, Read, Write}, path::{Path, PathBuf}, str::FromStr};
 
+mod batch;
 mod cli;
-mod chunk;
-mod chunk_type;
-mod png;
+#[cfg(feature = "clipboard")]
+mod clipboard;
+mod color;
+mod shell;
+mod timing;
+mod watch;
 
 pub type Error = Box<dyn std::error::Error>;
 pub type Result<T> = std::result::Result<T,Error>;
 
 
-fn load_png(path: String) -> std::result::Result<Png, ()> {
+/// Reads the png at `path`, starting from the byte `input` points at.
+fn load_png(path: String, input: &InputOptions) -> Result<Png> {
+    let bytes = timing::timed("read", || std::fs::read(&path))
+        .map_err(|err| format!("Unable to load png file {}: {}", path, err))?;
+    debug!("loaded {} ({} bytes)", path, bytes.len());
+
+    let bytes = if gzip::is_gzip(&bytes) {
+        let bytes = gzip::decompress(&bytes).map_err(|err| format!("Unable to decompress {}: {}", path, err))?;
+        debug!("decompressed {} to {} bytes", path, bytes.len());
+        if input.offset.is_none() && !input.scan && !bytes.starts_with(&Png::STANDARD_HEADER) {
+            return Err(format!("{} is gzip-compressed but doesn't contain a png", path).into());
+        }
+        bytes
+    }
+    else {
+        bytes
+    };
+
+    let bytes = embedded_png(&bytes, input).ok_or_else(|| format!("No png found in {}", path))?;
+    let options = ParseOptions {
+        max_chunks: input.max_chunks.unwrap_or(Png::DEFAULT_MAX_CHUNKS),
+        check_crc: !input.no_crc_check,
+        repair_lengths: input.repair_lengths,
+    };
+    let png = timing::timed("parse", || Png::from_bytes_with_options(bytes, &options))
+        .map_err(|err| format!("Unable to read png {}: {}", path, err))?;
+    debug!("parsed {} chunks", png.chunks().len());
+    if png.is_apple_optimized() {
+        eprintln!("warning: {} is an Apple CgBI png; other decoders can't read its image data", path);
+    }
+    Ok(png)
+}
+
+/// Slices out the png selected by `--offset` or `--scan`. When scanning finds several signatures,
+/// each offset is reported and the first png (up to the next signature) is used.
+fn embedded_png<'a>(bytes: &'a [u8], input: &InputOptions) -> Option<&'a [u8]> {
+    if let Some(offset) = input.offset {
+        return bytes.get(offset..);
+    }
+    if !input.scan {
+        return Some(bytes);
+    }
+
+    let offsets = Png::find_signatures(bytes);
+    if offsets.len() > 1 {
+        let offsets: Vec<String> = offsets.iter().map(|offset| offset.to_string()).collect();
+        eprintln!("found png signatures at offsets {}; reading the first", offsets.join(", "));
+    }
+    let start = *offsets.first()?;
+    let end = offsets.get(1).copied().unwrap_or(bytes.len());
+    debug!("reading embedded png at bytes {}..{}", start, end);
+    Some(&bytes[start..end])
+}
+
+/// How many bytes [`save_png`] would write for `png` at `path`, compressing it first if `path`
+/// ends in `.gz`.
+fn estimated_size(png: &Png, path: &str) -> usize {
+    if is_gzip_path(path) {
+        gzip::compress(&png.as_bytes()).len()
+    }
+    else {
+        png.encoded_len()
+    }
+}
+
+/// The output options for `encode`, where `--force` also silences the warning about an output
+/// path that doesn't end in .png.
+fn encode_output_options(output: &OutputOptions, options: &EncodeOptions) -> OutputOptions {
+    OutputOptions { no_extension_check: output.no_extension_check || options.force, ..*output }
+}
+
+/// Whether `path` should be written gzip-compressed, i.e. ends in `.gz`.
+fn is_gzip_path(path: &str) -> bool {
+    Path::new(path).extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gz"))
+}
+
+/// Writes `png` to `path` (or stdout when `path` is `-`), gzip-compressed when `path` ends in
+/// `.gz`, returning the number of bytes written.
+fn save_png(png: &Png, path: String, options: &OutputOptions) -> Result<usize> {
+    let canonical;
+    let png = if options.canonical {
+        canonical = canonicalized(png);
+        &canonical
+    }
+    else {
+        png
+    };
+
+    // the chunks stream straight to the file unless they need compressing first
+    let compressed = is_gzip_path(&path).then(|| gzip::compress(&png.as_bytes()));
+    let size = compressed.as_ref().map_or_else(|| png.encoded_len(), Vec::len);
+    write_output(&path, size, options, |out| match &compressed {
+        Some(bytes) => out.write_all(bytes),
+        None => png.write_to(&mut &mut *out)
+    })
+}
+
+/// Like [`save_png`] for a png read from `source`, copying the permissions of `source` to
+/// `path` with `--preserve-mode`. Saving over `source` itself already keeps them.
+fn save_png_from(png: &Png, source: &str, path: String, options: &OutputOptions) -> Result<usize> {
+    let written = save_png(png, path.clone(), options)?;
+    preserve_mode(source, &path, options)?;
+    Ok(written)
+}
+
+/// Like [`save_png_from`] for each of `paths`, encoding `png` only once. A failed write doesn't
+/// stop the rest; the result for each path is returned in order.
+fn save_png_to_each(png: &Png, source: &str, paths: &[String], options: &OutputOptions) -> Vec<Result<usize>> {
+    let canonical;
+    let png = if options.canonical {
+        canonical = canonicalized(png);
+        &canonical
+    }
+    else {
+        png
+    };
+
+    let bytes = png.as_bytes();
+    let compressed = OnceCell::new();
+    paths.iter().map(|path| {
+        let body: &[u8] = if is_gzip_path(path) { compressed.get_or_init(|| gzip::compress(&bytes)) } else { &bytes };
+        let written = write_output(path, body.len(), options, |out| out.write_all(body))?;
+        preserve_mode(source, path, options)?;
+        Ok(written)
+    }).collect()
+}
+
+/// A copy of `png` in the canonical chunk order `--canonical` asks for.
+fn canonicalized(png: &Png) -> Png {
+    let mut sorted = Png::from_chunks(png.chunks().to_vec());
+    sorted.canonicalize();
+    sorted
+}
+
+/// Creates `path` (or writes to stdout when `path` is `-`) and fills it with the `size` bytes
+/// `write_body` produces, applying the `--progress`, `--preserve-times` and `--verify-output`
+/// options.
+fn write_output(path: &str, size: usize, options: &OutputOptions, write_body: impl Fn(&mut dyn Write) -> std::io::Result<()>) -> Result<usize> {
+    if !options.no_extension_check {
+        if let Some(warning) = extension_warning(path) {
+            eprintln!("{}", warning);
+        }
+    }
+
+    let times = if options.preserve_times && path != "-" { file_times(path) } else { None };
+
+    timing::timed("write", || -> std::io::Result<()> {
+        let out: Box<dyn Write> = if path == "-" { Box::new(std::io::stdout().lock()) } else { Box::new(std::fs::File::create(path)?) };
+        if options.progress {
+            let mut out = BufWriter::with_capacity(progress::BLOCK_SIZE, ProgressWriter::new(out, std::io::stderr(), size));
+            write_body(&mut out)?;
+            out.into_inner().map_err(|err| err.into_error())?.finish()
+        }
+        else {
+            let mut out = BufWriter::new(out);
+            write_body(&mut out)?;
+            out.flush()
+        }
+    })?;
+    debug!("saved {} bytes to {}", size, path);
+
+    if let Some(times) = times {
+        std::fs::File::options().write(true).open(path)?.set_times(times)?;
+        debug!("restored timestamps of {}", path);
+    }
+
+    if options.verify_output && path != "-" {
+        verify_saved(path, size)?;
+    }
+    Ok(size)
+}
+
+/// Copies the permissions of `source` to `path` with `--preserve-mode`.
+fn preserve_mode(source: &str, path: &str, options: &OutputOptions) -> Result<()> {
+    if options.preserve_mode && path != "-" && path != source {
+        std::fs::set_permissions(path, std::fs::metadata(source)?.permissions())?;
+        debug!("copied the permissions of {} to {}", source, path);
+    }
+    Ok(())
+}
+
+/// The access and modification times of an existing file, or `None` if it can't be read.
+fn file_times(path: &str) -> Option<FileTimes> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some(FileTimes::new().set_accessed(metadata.accessed().ok()?).set_modified(metadata.modified().ok()?))
+}
+
+/// A warning for output paths that don't end in `.png`, which viewers may not recognize.
+fn extension_warning(path: &str) -> Option<String> {
+    let path = if is_gzip_path(path) { &path[..path.len() - 3] } else { path };
+    let is_png = std::path::Path::new(path).extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+    if path == "-" || is_png {
+        None
+    }
+    else {
+        Some(format!("warning: {} does not end in .png; pass --no-extension-check to silence this", path))
+    }
+}
+
+/// Reads back the file just written to `path` and checks it is a complete, parseable png.
+fn verify_saved(path: &str, expected_len: usize) -> Result<()> {
+    let bytes = std::fs::read(path)?;
+    if bytes.len() != expected_len {
+        return Err(format!("Verifying {} failed: wrote {} bytes but found {}", path, expected_len, bytes.len()).into());
+    }
+    let bytes = if is_gzip_path(path) {
+        gzip::decompress(&bytes).map_err(|err| format!("Verifying {} failed: {}", path, err))?
+    }
+    else {
+        bytes
+    };
+    Png::try_from(bytes.as_slice()).map_err(|err| format!("Verifying {} failed: {}", path, err))?;
+    debug!("verified {}", path);
+    Ok(())
+}
+
+fn encode(png: &mut Png, chunk_type: &str, message: &[u8], options: &EncodeOptions) -> Result<()> {
+    let chunk_type = match options.raw_type {
+        Some(bytes) => ChunkType::from_bytes_unchecked(bytes),
+        None => ChunkType::from_str(chunk_type)?
+    };
+
+    if chunk_type.is_critical() && !options.force {
+        let reason = if chunk_type.as_str().is_some_and(|name| ChunkType::CRITICAL_TYPES.contains(&name)) {
+            "writing into it will corrupt the image"
+        }
+        else {
+            "decoders will reject the image if they don't know it"
+        };
+        return Err(format!(
+            "Refusing to encode into critical chunk type {}: {}. Use an ancillary type like ruSt, or pass --force.",
+            chunk_type, reason
+        ).into());
+    }
+
+    let message = encoding::encode(message, options.encoding);
+    let pieces = match options.split {
+        Some(size) => split::split_message(&message, size),
+        None => vec!(message)
+    };
+
+    let is_color_type = chunk_type.as_str().is_some_and(|name| ChunkType::COLOR_TYPES.contains(&name));
+    for data in pieces {
+        let chunk = Chunk::new(chunk_type, data);
+        debug!("computed crc {:08x} for {} byte chunk {}", chunk.crc(), chunk.length(), chunk.chunk_type());
+        if is_color_type {
+            png.insert_before_idat(chunk)?;
+        }
+        else {
+            png.append_chunk(chunk);
+        }
+    }
+    Ok(())
+}
+
+/// A warning for binary messages stored as-is, which `decode` can't print later.
+fn utf8_warning(message: &[u8], options: &EncodeOptions) -> Option<String> {
+    if options.allow_invalid_utf8 || options.hex || options.encoding != encoding::Encoding::Raw {
+        return None;
+    }
+    let err = std::str::from_utf8(message).err()?;
+    Some(format!(
+        "warning: the message is not valid UTF-8 ({}); read it back with `decode-at <index> --raw`, \
+        or pass --allow-invalid-utf8 to silence this",
+        err
+    ))
+}
+
+/// The bytes of an encode message, read from `stdin` to EOF when the message is `-`.
+fn read_message(message: &str, mut stdin: impl Read) -> std::io::Result<Vec<u8>> {
+    if message != "-" {
+        return Ok(message.as_bytes().to_vec());
+    }
+    let mut bytes = vec!();
+    stdin.read_to_end(&mut bytes)?;
+    debug!("read {} message bytes from stdin", bytes.len());
+    Ok(bytes)
+}
+
+/// The output path `--output-template` gives for `input`: the template with `{stem}`, `{ext}`
+/// and `{type}` filled in, in the same directory as `input`.
+fn expand_output_template(template: &str, input: &str, chunk_type: &str) -> Result<String> {
+    let input = Path::new(input);
+    let stem = input.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+    let ext = input.extension().map(|ext| ext.to_string_lossy()).unwrap_or_default();
+
+    let mut name = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        name.push_str(&rest[..open]);
+        let close = rest[open..].find('}')
+            .ok_or_else(|| format!("Unclosed {{ in --output-template {:?}", template))?;
+        name.push_str(match &rest[open + 1..open + close] {
+            "stem" => &stem,
+            "ext" => &ext,
+            "type" => chunk_type,
+            other => return Err(format!("Unknown placeholder {{{}}} in --output-template; expected {{stem}}, {{ext}} or {{type}}", other).into())
+        });
+        rest = &rest[open + close + 1..];
+    }
+    name.push_str(rest);
+
+    let dir = input.parent().unwrap_or(Path::new(""));
+    Ok(dir.join(name).to_string_lossy().into_owned())
+}
+
+/// The bytes spelled by a `--hex` message, ignoring surrounding whitespace such as a trailing
+/// newline from stdin.
+fn hex_message(message: &[u8]) -> Result<Vec<u8>> {
+    encoding::decode_hex(message.trim_ascii()).map_err(|err| format!("Invalid --hex message: {}", err).into())
+}
+
+/// Whether `chunk` passes a `--type` filter; an empty filter lets everything through.
+fn type_filter_matches(chunk: &Chunk, types: &[String]) -> bool {
+    types.is_empty() || types.iter().any(|pattern| chunk.chunk_type().matches(pattern))
+}
+
+fn print(png: &Png, options: &PrintOptions, palette: &Palette, out: &mut impl Write) -> std::io::Result<()> {
+    let mut chunks: Vec<&Chunk> = png.chunks().iter()
+        .filter(|chunk| !options.custom || !chunk.chunk_type().is_known())
+        .filter(|chunk| type_filter_matches(chunk, &options.types))
+        .collect();
+
+    let start = match options.tail {
+        Some(tail) => chunks.len().saturating_sub(tail),
+        None => 0
+    };
+    let mut chunks = chunks.split_off(start);
+    if options.sort {
+        chunks.sort_by_key(|chunk| chunk.chunk_type());
+    }
+    if options.breakdown {
+        return write_breakdown(out, png.encoded_len(), &chunks);
+    }
+    if options.stats {
+        return write_stats(out, &chunks);
+    }
+
+    writeln!(out, "Png {{[")?;
+    for chunk in chunks {
+        write!(out, "   ")?;
+        color::write_chunk(out, chunk, palette)?;
+        writeln!(out)?;
+        if options.hash {
+            writeln!(out, "   Sha256: {}", sha256::sha256_hex(chunk.data()))?;
+        }
+        if options.hexdump {
+            writeln!(out, "{}", hexdump::hexdump_truncated(chunk.data(), options.hexdump_width, options.max_bytes))?;
+        }
+    }
+    writeln!(out, "]}}")?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// A warning for each ancillary chunk of `png` holding more than `limit` bytes, for `--warn-size`.
+fn size_warnings(png: &Png, limit: u32) -> Vec<String> {
+    verify::oversized_ancillary_chunks(png, limit).iter()
+        .map(|(index, chunk)| format!("warning: chunk {}: {} holds {} bytes, more than {}", index, chunk.chunk_type(), chunk.length(), limit))
+        .collect()
+}
+
+/// Writes each chunk's size, including its 12 bytes of length, type and crc, as a share of
+/// `file_size`, largest first.
+fn write_breakdown(out: &mut impl Write, file_size: usize, chunks: &[&Chunk]) -> std::io::Result<()> {
+    let mut sizes: Vec<(&Chunk, usize)> = chunks.iter().map(|chunk| (*chunk, chunk.length() as usize + 12)).collect();
+    sizes.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+
+    writeln!(out, "{} bytes in total", file_size)?;
+    for (chunk, size) in sizes {
+        writeln!(out, "{}  {:>10} bytes  {:>6.2}%", chunk.chunk_type(), size, size as f64 * 100.0 / file_size as f64)?;
+    }
+    Ok(())
+}
+
+/// Writes how many of `chunks` have each chunk type property, with private critical chunks
+/// counted separately since no standard chunk is one.
+fn write_stats(out: &mut impl Write, chunks: &[&Chunk]) -> std::io::Result<()> {
+    let count = |predicate: fn(&ChunkType) -> bool| chunks.iter().filter(|chunk| predicate(chunk.chunk_type())).count();
+    let critical = count(ChunkType::is_critical);
+    let public = count(ChunkType::is_public);
+    let private_critical = count(|chunk_type| chunk_type.is_critical() && !chunk_type.is_public());
+
+    writeln!(out, "{} chunks", chunks.len())?;
+    writeln!(out, "critical:          {}", critical)?;
+    writeln!(out, "ancillary:         {}", chunks.len() - critical)?;
+    writeln!(out, "public:            {}", public)?;
+    writeln!(out, "private:           {}", chunks.len() - public)?;
+    writeln!(out, "safe to copy:      {}", count(ChunkType::is_safe_to_copy))?;
+    writeln!(out, "private critical:  {}", private_critical)
+}
+
+/// With `ignore_case`, swaps `chunk_type` for the first stored type that spells the same letters.
+fn resolve_chunk_type(png: &Png, chunk_type: &str, ignore_case: bool) -> String {
+    if ignore_case {
+        if let Ok(wanted) = ChunkType::from_str(chunk_type) {
+            if let Some(chunk) = png.iter().find(|chunk| chunk.chunk_type().eq_ignore_case(&wanted)) {
+                return chunk.chunk_type().to_string();
+            }
+        }
+    }
+    chunk_type.to_string()
+}
+
+fn decode_message(data: &[u8], options: &DecodeOptions) -> Result<String> {
+    Ok(String::from_utf8(encoding::decode(data, options.encoding)?)?)
+}
+
+/// Prints the message stored under `chunk_type`, returning how many chunks were decoded.
+/// A glob pattern prints every matching chunk as `type: message`.
+fn decode(png: &Png, chunk_type: &str, options: &DecodeOptions, out: &mut impl Write) -> Result<usize> {
+    let chunk_type = &resolve_chunk_type(png, chunk_type, options.ignore_case);
+    if options.split {
+        return decode_split(png, chunk_type, options, out);
+    }
+    if options.recurse {
+        return decode_nested(png, chunk_type, options, &mut vec!(), out);
+    }
+
+    if ChunkType::is_pattern(chunk_type) {
+        let chunks = png.chunks_by_type(chunk_type);
+        for chunk in chunks.iter() {
+            writeln!(out, "{}: {}", chunk.chunk_type(), decode_message(chunk.data(), options)?)?;
+        }
+        Ok(chunks.len())
+    }
+    else if let Some(chunk) = png.chunk_by_type(chunk_type) {
+        writeln!(out, "{}", decode_message(chunk.data(), options)?)?;
+        Ok(1)
+    }
+    else {
+        Ok(0)
+    }
+}
+
+/// Puts the decoded `text` on the clipboard with `copy`, or prints it with a warning when that fails.
+#[cfg(feature = "clipboard")]
+fn copy_or_print(
+    text: &[u8],
+    copy: impl FnOnce(&str) -> std::result::Result<(), String>,
+    out: &mut impl Write,
+    err: &mut impl Write,
+) -> std::io::Result<()> {
+    let message = String::from_utf8_lossy(text);
+    match copy(message.strip_suffix('\n').unwrap_or(&message)) {
+        Ok(()) => writeln!(err, "copied to the clipboard"),
+        Err(reason) => {
+            writeln!(err, "warning: {}; printing instead", reason)?;
+            out.write_all(text)
+        }
+    }
+}
+
+/// Prints `path: message` for every chunk matching `chunk_type`, descending into chunks whose data
+/// is itself a png up to `--max-depth` levels. The path names each chunk on the way by index and
+/// type, e.g. `2:ruSt/1:teXt`.
+fn decode_nested(png: &Png, chunk_type: &str, options: &DecodeOptions, path: &mut Vec<String>, out: &mut impl Write) -> Result<usize> {
+    let mut found = 0;
+    for (idx, chunk) in png.iter().enumerate() {
+        path.push(format!("{}:{}", idx, chunk.chunk_type()));
+        if chunk.data().starts_with(&Png::STANDARD_HEADER) {
+            if path.len() > options.max_depth {
+                eprintln!("warning: not descending into {}: --max-depth {} reached", path.join("/"), options.max_depth);
+            }
+            else if let Ok(nested) = Png::try_from(chunk.data()) {
+                found += decode_nested(&nested, chunk_type, options, path, out)?;
+            }
+        }
+        else if chunk.chunk_type().matches(chunk_type) {
+            writeln!(out, "{}: {}", path.join("/"), decode_message(chunk.data(), options)?)?;
+            found += 1;
+        }
+        path.pop();
+    }
+    Ok(found)
+}
+
+/// Decodes `path` as the `decode` command does, failing when nothing matched.
+fn decode_command(path: &str, chunk_type: &str, options: &DecodeOptions, input: &InputOptions) -> Result<()> {
+    let png = if options.stream { None } else { Some(load_png(path.to_string(), input)?) };
+    #[cfg(feature = "clipboard")]
+    let mut captured = Vec::new();
+    let mut out: Box<dyn Write + '_> = if options.count { Box::new(std::io::sink()) } else { Box::new(std::io::stdout()) };
+    #[cfg(feature = "clipboard")]
+    if options.clipboard {
+        out = Box::new(&mut captured);
+    }
+    let found = match png {
+        None => {
+            let file = std::fs::File::open(path).map_err(|err| format!("Unable to load png file {}: {}", path, err))?;
+            decode_stream(std::io::BufReader::new(file), chunk_type, options, &mut out)?
+        },
+        Some(png) if options.all => decode_all(&png, options, &mut out)?,
+        Some(png) => decode(&png, chunk_type, options, &mut out)?
+    };
+    drop(out);
+    #[cfg(feature = "clipboard")]
+    if options.clipboard && found > 0 {
+        copy_or_print(&captured, clipboard::copy, &mut std::io::stdout(), &mut std::io::stderr())?;
+    }
+    if options.count {
+        println!("{}", found);
+    }
+    // a non-zero exit lets scripts tell whether anything was found
+    if found == 0 {
+        return Err("Nothing to decode".into());
+    }
+    Ok(())
+}
+
+/// Prints the message of each chunk matching `chunk_type` as the chunk is read from `reader`,
+/// flushing after each one. With `--first`, stops reading at the first match.
+fn decode_stream(reader: impl Read, chunk_type: &str, options: &DecodeOptions, out: &mut impl Write) -> Result<usize> {
+    let mut found = 0;
+    for chunk in Png::stream_chunks(reader)? {
+        let chunk = chunk?;
+        if !chunk.chunk_type().matches(chunk_type) {
+            continue;
+        }
+        writeln!(out, "{}", decode_message(chunk.data(), options)?)?;
+        out.flush()?;
+        found += 1;
+        if options.first {
+            break;
+        }
+    }
+    Ok(found)
+}
+
+/// Prints the message that was split across every chunk of `chunk_type`.
+fn decode_split(png: &Png, chunk_type: &str, options: &DecodeOptions, out: &mut impl Write) -> Result<usize> {
+    let pieces: Vec<&[u8]> = png.chunks_by_type(chunk_type).iter().map(|chunk| chunk.data()).collect();
+    if pieces.is_empty() {
+        return Ok(0);
+    }
+    let message = decode_message(&split::join_pieces(&pieces)?, options)?;
+    writeln!(out, "{}", message)?;
+    Ok(pieces.len())
+}
+
+/// Prints `type: message` for every chunk whose data decodes to valid UTF-8, returning how many were printed.
+fn decode_all(png: &Png, options: &DecodeOptions, out: &mut impl Write) -> Result<usize> {
+    let mut count = 0;
+    for chunk in png.chunks() {
+        if chunk.data().is_empty() || (chunk.chunk_type().is_critical() && !options.include_critical) {
+            continue;
+        }
+        if let Ok(message) = decode_message(chunk.data(), options) {
+            writeln!(out, "{}: {}", chunk.chunk_type(), message)?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Prints the data of the chunk at `index`.
+fn decode_at(png: &Png, index: usize, options: &DecodeAtOptions, out: &mut impl Write) -> Result<()> {
+    let data = png.chunk_at(index)?.data();
+    if options.raw {
+        out.write_all(data)?;
+    }
+    else if options.lossy {
+        writeln!(out, "{}", String::from_utf8_lossy(data))?;
+    }
+    else {
+        writeln!(out, "{}", std::str::from_utf8(data)?)?;
+    }
+    Ok(())
+}
+
+/// Stores `text`, replacing the `iTXt` chunk with the same keyword in place or else adding one before the first `IDAT`.
+fn set_itxt(png: &mut Png, text: &InternationalText) -> Result<()> {
+    let chunk = text.to_chunk()?;
+    let existing = png.iter().position(|chunk| {
+        InternationalText::try_from(chunk).is_ok_and(|existing| existing.keyword == text.keyword)
+    });
+    match existing {
+        Some(idx) => {
+            png.remove_chunk_at(idx)?;
+            png.insert_chunk_at(idx, chunk)?;
+        },
+        None => png.insert_before_idat(chunk)?
+    }
+    Ok(())
+}
+
+/// Prints the text of every `iTXt` chunk matching `keyword`, or all of them as `keyword: text`.
+fn get_itxt(png: &Png, keyword: Option<&str>, out: &mut impl Write) -> Result<usize> {
+    let mut count = 0;
+    for chunk in png.chunks_by_type("iTXt") {
+        let text = InternationalText::try_from(chunk)?;
+        match keyword {
+            Some(keyword) if keyword == text.keyword => writeln!(out, "{}", text.text)?,
+            Some(_) => continue,
+            None => writeln!(out, "{}: {}", text.keyword, text.text)?,
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Stores `text` in place of the `zTXt` chunk with the same keyword, or before the first `IDAT` if there is none.
+fn set_ztxt(png: &mut Png, text: &CompressedText) -> Result<()> {
+    let chunk = text.to_chunk()?;
+    let existing = png.iter().position(|chunk| {
+        CompressedText::try_from(chunk).is_ok_and(|existing| existing.keyword == text.keyword)
+    });
+    match existing {
+        Some(idx) => {
+            png.remove_chunk_at(idx)?;
+            png.insert_chunk_at(idx, chunk)?;
+        },
+        None => png.insert_before_idat(chunk)?
+    }
+    Ok(())
+}
+
+/// Prints the text of every `zTXt` chunk with `keyword`.
+fn get_ztxt(png: &Png, keyword: &str, out: &mut impl Write) -> Result<usize> {
+    let mut count = 0;
+    for chunk in png.chunks_by_type("zTXt") {
+        let text = CompressedText::try_from(chunk)?;
+        if text.keyword == keyword {
+            writeln!(out, "{}", text.text)?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+const META_CHUNK_TYPE: &str = "meTa";
+
+/// The entries of the JSON object in the `meTa` chunk, or none if there is no such chunk.
+fn read_meta(png: &Png) -> Result<Vec<(String, Json)>> {
+    let chunk = match png.chunk_by_type(META_CHUNK_TYPE) {
+        Some(chunk) => chunk,
+        None => return Ok(vec!())
+    };
+    match json::parse(&chunk.data_as_string()?)? {
+        Json::Object(entries) => Ok(entries),
+        _ => Err(format!("The {} chunk does not hold a JSON object", META_CHUNK_TYPE).into())
+    }
+}
+
+/// Sets `key` to the string `value` in the `meTa` chunk, adding the chunk before the first `IDAT`
+/// if it's missing, like the other metadata writers.
+fn meta_set(png: &mut Png, key: &str, value: &str) -> Result<()> {
+    let mut entries = read_meta(png)?;
+    match entries.iter_mut().find(|(name, _)| name == key) {
+        Some((_, existing)) => *existing = Json::from(value),
+        None => entries.push((key.to_string(), Json::from(value)))
+    }
+    let data = Json::Object(entries).to_string().into_bytes();
+
+    if png.chunk_by_type(META_CHUNK_TYPE).is_some() {
+        png.replace_chunk(META_CHUNK_TYPE, data)?;
+    }
+    else {
+        png.insert_before_idat(Chunk::new(ChunkType::from_str(META_CHUNK_TYPE)?, data))?;
+    }
+    Ok(())
+}
+
+/// The value of `key` in the `meTa` chunk; strings come back without their JSON quotes.
+fn meta_get(png: &Png, key: &str) -> Result<Option<String>> {
+    Ok(read_meta(png)?.into_iter()
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| meta_value_string(&value)))
+}
+
+fn meta_value_string(value: &Json) -> String {
+    match value.as_str() {
+        Some(value) => value.to_string(),
+        None => value.to_string()
+    }
+}
+
+fn write_list_entry(out: &mut impl Write, index: usize, chunk: &Chunk, hash: bool) -> std::io::Result<()> {
+    write!(out, "{:>5}  {}  {:>10} bytes  crc {:08x}", index, chunk.chunk_type(), chunk.length(), chunk.crc())?;
+    if hash {
+        write!(out, "  sha256 {}", sha256::sha256_hex(chunk.data()))?;
+    }
+    match chunk.chunk_type().description() {
+        Some(description) => writeln!(out, "  {}", description),
+        None => writeln!(out),
+    }
+}
+
+fn list(png: &Png, options: &ListOptions, out: &mut impl Write) -> std::io::Result<()> {
+    let mut chunks: Vec<(usize, &Chunk)> = png.chunks().iter()
+        .enumerate()
+        .filter(|(_, chunk)| type_filter_matches(chunk, &options.types))
+        .collect();
+    if options.sort {
+        chunks.sort_by_key(|(_, chunk)| chunk.chunk_type());
+    }
+
+    let total = chunks.len();
+    let start = options.skip.unwrap_or(0).min(total);
+    let end = options.limit.map_or(total, |limit| start.saturating_add(limit).min(total));
+    let mut chunks = chunks[start..end].to_vec();
+
+    // with --head/--tail, remember where the dropped middle was and how much of it there was
+    let mut omitted = None;
+    if options.head.is_some() || options.tail.is_some() {
+        let (head, tail) = (options.head.unwrap_or(0), options.tail.unwrap_or(0));
+        if head.saturating_add(tail) < total {
+            chunks.drain(head..total - tail);
+            omitted = Some((head, total - head - tail));
+        }
+    }
+    let chunks = chunks.as_slice();
+
+    match options.format {
+        ListFormat::Table => {},
+        ListFormat::Csv => return write_list_csv(out, chunks),
+        ListFormat::Json if options.pretty => return writeln!(out, "{}", list_json(chunks).to_string_pretty()),
+        ListFormat::Json => return writeln!(out, "{}", list_json(chunks)),
+    }
+
+    if options.group_by_type {
+        let mut groups: BTreeMap<&ChunkType, Vec<(usize, &Chunk)>> = BTreeMap::new();
+        for (index, chunk) in chunks {
+            groups.entry(chunk.chunk_type()).or_default().push((*index, chunk));
+        }
+
+        for (chunk_type, chunks) in groups {
+            writeln!(out, "{} ({})", chunk_type, chunks.len())?;
+            for (index, chunk) in chunks {
+                write_list_entry(out, index, chunk, options.hash)?;
+            }
+        }
+    }
+    else {
+        for position in 0..=chunks.len() {
+            if let Some((_, count)) = omitted.filter(|(at, _)| *at == position) {
+                writeln!(out, "... {} chunks omitted ...", count)?;
+            }
+            if let Some((index, chunk)) = chunks.get(position) {
+                write_list_entry(out, *index, chunk, options.hash)?;
+            }
+        }
+    }
+
+    if options.skip.is_some() || options.limit.is_some() {
+        writeln!(out, "showing {}..{} of {}", start, end, total)?;
+    }
+    Ok(())
+}
+
+/// Quotes a CSV field when it holds a comma, quote or line break, doubling any quotes.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    }
+    else {
+        field.to_string()
+    }
+}
+
+fn write_list_csv(out: &mut impl Write, chunks: &[(usize, &Chunk)]) -> std::io::Result<()> {
+    writeln!(out, "index,type,length,crc,critical,public,safe_to_copy,known")?;
+    for (index, chunk) in chunks {
+        let chunk_type = chunk.chunk_type();
+        writeln!(out, "{},{},{},{:08x},{},{},{},{}",
+            index, csv_field(&chunk_type.to_string()), chunk.length(), chunk.crc(),
+            chunk_type.is_critical(), chunk_type.is_public(), chunk_type.is_safe_to_copy(), chunk_type.is_known())?;
+    }
+    Ok(())
+}
+
+fn list_json(chunks: &[(usize, &Chunk)]) -> Json {
+    Json::Array(chunks.iter().map(|(index, chunk)| {
+        let chunk_type = chunk.chunk_type();
+        Json::Object(vec![
+            (String::from("index"), Json::from(*index)),
+            (String::from("type"), Json::from(chunk_type.to_string())),
+            (String::from("length"), Json::from(chunk.length())),
+            (String::from("crc"), Json::from(chunk.crc())),
+            (String::from("critical"), Json::from(chunk_type.is_critical())),
+            (String::from("known"), Json::from(chunk_type.is_known())),
+            (String::from("public"), Json::from(chunk_type.is_public())),
+            (String::from("safe_to_copy"), Json::from(chunk_type.is_safe_to_copy())),
+        ])
+    }).collect())
+}
+
+/// Writes the data of every chunk passing the `--type` filter to `<index>_<type>.bin` in `out_dir`,
+/// returning the files written. The index prefix keeps chunks of the same type apart.
+fn extract(png: &Png, out_dir: &Path, types: &[String]) -> std::io::Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(out_dir)?;
+    let mut written = vec!();
+    for (index, chunk) in png.chunks().iter().enumerate() {
+        if !type_filter_matches(chunk, types) {
+            continue;
+        }
+        let file = out_dir.join(format!("{:04}_{}.bin", index, chunk.chunk_type()));
+        std::fs::write(&file, chunk.data())?;
+        debug!("extracted {} bytes to {}", chunk.data().len(), file.display());
+        written.push(file);
+    }
+    Ok(written)
+}
+
+/// Decides whether to go ahead with overwriting a file. `--yes`/`--no` answer up front; otherwise
+/// an interactive session is asked `question` and anything but `y`/`yes` declines, while piped
+/// runs proceed without asking.
+fn confirm(question: &str, yes: bool, no: bool, interactive: bool, mut input: impl BufRead, out: &mut impl Write) -> std::io::Result<bool> {
+    if yes || no {
+        return Ok(yes);
+    }
+    if !interactive {
+        return Ok(true);
+    }
+    write!(out, "{} [y/N] ", question)?;
+    out.flush()?;
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Describes what a `--dry-run` would have written, e.g. `would append chunk ruSt (12 bytes), new file size 1234 bytes`.
+fn dry_run_summary(action: &str, chunk_type: &str, bytes: usize, png: &Png) -> String {
+    dry_run_report(&format!("{} chunk {} ({} bytes)", action, chunk_type, bytes), png)
+}
 
-    match std::fs::read(path) {
-        Ok(bytes) => Png::try_from(bytes.as_slice()),
-        Err(err) => panic!("Unable to load png file: {}", err) 
+/// The difference between two sizes with its sign, e.g. `+12 bytes` or `-3 bytes`, since
+/// replacing chunks can shrink a file.
+fn size_change(before: usize, after: usize) -> String {
+    if after >= before {
+        format!("+{} bytes", after - before)
+    }
+    else {
+        format!("-{} bytes", before - after)
     }
 }
 
-fn save_png(png: Png, path: String) -> std::result::Result<(), std::io::Error> {
-    std::fs::write(path, png.as_bytes())
+/// Like [`dry_run_summary`] for edits that don't concern a single chunk, e.g.
+/// `would strip 3 chunks (40 bytes), new file size 1234 bytes`.
+fn dry_run_report(description: &str, png: &Png) -> String {
+    format!("would {}, new file size {} bytes", description, png.encoded_len())
 }
 
 fn main() -> Result<()>{
+    run(Cli::parse())
+}
+
+fn run(args: Cli) -> Result<()> {
 
-    let args = Cli::parse();
+    logging::init(args.verbose);
+    timing::init(args.timing);
 
     match args.command {
 
-        cli::Commands::Encode { path, chunk_type, message, output_file } => {
+        cli::Commands::Encode { path, chunk_type, message, output_file, options } => {
+            let output_options = encode_output_options(&args.output, &options);
 
-            let mut png = load_png(path).expect("Unable to read png.");
-            let chunk_type = ChunkType::from_str(&chunk_type).expect("Invalid chunk_type");
-            let chunk = Chunk::new(chunk_type, message.as_bytes().to_vec());
-            png.append_chunk(chunk);
-            save_png(png, output_file).expect("Error saving output file");
+            let output_file = match &options.output_template {
+                Some(template) => expand_output_template(template, &path, &chunk_type)?,
+                None => output_file
+            };
+            let targets = if options.outputs.is_empty() { vec!(output_file) } else { options.outputs.clone() };
+            if options.no_overwrite && !options.estimate {
+                if let Some(existing) = targets.iter().find(|target| *target != "-" && Path::new(target).exists()) {
+                    return Err(format!("{} already exists; pass --overwrite to replace it", existing).into());
+                }
+            }
+            let mut png = load_png(path.clone(), &args.input)?;
+            let mut message = read_message(&message, std::io::stdin().lock())?;
+            if options.hex {
+                message = hex_message(&message)?;
+            }
+            if let Some(warning) = utf8_warning(&message, &options) {
+                eprintln!("{}", warning);
+            }
+            let before = png.total_data_size();
+            let input_len = png.encoded_len();
+            timing::timed("encode", || encode(&mut png, &chunk_type, &message, &options))?;
+            if options.recompute_all_crcs {
+                png.recompute_crcs();
+            }
+            if options.estimate {
+                for target in targets.iter() {
+                    println!("would write {} bytes to {} (the input png is {} bytes)", estimated_size(&png, target), target, input_len);
+                }
+                return Ok(());
+            }
+            if args.dry_run {
+                println!("{}", dry_run_summary("append", &chunk_type, png.total_data_size() - before, &png));
+                return Ok(());
+            }
+            if let [output_file] = targets.as_slice() {
+                let written = save_png_from(&png, &path, output_file.clone(), &output_options)?;
+                if output_file == "-" {
+                    eprintln!("wrote {} bytes to stdout", written);
+                }
+                else {
+                    println!("wrote {} bytes to {}", written, output_file);
+                }
+                return Ok(());
+            }
+            let results = save_png_to_each(&png, &path, &targets, &output_options);
+            let mut failed = 0;
+            for (target, result) in targets.iter().zip(results) {
+                match result {
+                    Ok(written) if target == "-" => eprintln!("wrote {} bytes to stdout", written),
+                    Ok(written) => println!("wrote {} bytes to {}", written, target),
+                    Err(err) => {
+                        eprintln!("Unable to write {}: {}", target, err);
+                        failed += 1;
+                    }
+                }
+            }
+            if failed > 0 {
+                return Err(format!("{} of {} outputs could not be written", failed, targets.len()).into());
+            }
+        },
+
+        cli::Commands::Decode { path, chunk_type, options } => {
+            let chunk_type = chunk_type.unwrap_or_default();
+            if !options.watch {
+                return decode_command(&path, &chunk_type, &options, &args.input);
+            }
+            let run_once = || {
+                if let Err(err) = decode_command(&path, &chunk_type, &options, &args.input) {
+                    eprintln!("{}", err);
+                }
+            };
+            run_once();
+            watch::watch(Path::new(&path), watch::POLL_INTERVAL, watch::DEBOUNCE, || {
+                println!("--- {} changed", path);
+                run_once();
+                std::ops::ControlFlow::Continue(())
+            });
+        },
+
+        cli::Commands::DecodeAt { path, index, options } => {
+            let png = load_png(path, &args.input)?;
+            decode_at(&png, index, &options, &mut std::io::stdout())?;
+        },
+
+        cli::Commands::Remove { path, chunk_type, ignore_case, last, all, force, yes, no } => {
+            let mut png = load_png(path.clone(), &args.input)?;
+            let chunk_type = resolve_chunk_type(&png, &chunk_type, ignore_case);
+            let (count, bytes) = if all {
+                let matching = png.chunks_by_type(&chunk_type);
+                if let Some(critical) = matching.iter().find(|chunk| chunk.chunk_type().is_critical()) {
+                    if !force {
+                        return Err(format!("Refusing to remove critical chunk {}. Pass --force to remove it anyway.", critical.chunk_type()).into());
+                    }
+                }
+                let bytes = matching.iter().map(|chunk| chunk.data_length()).sum();
+                let count = timing::timed("remove", || png.remove_all_chunks(&chunk_type));
+                if count == 0 {
+                    return Err(PngError::ChunkNotFound(chunk_type).into());
+                }
+                (count, bytes)
+            }
+            else {
+                let removed = timing::timed("remove", || if last { png.remove_last_chunk(&chunk_type) } else { png.remove_chunk(&chunk_type) });
+                (1, removed?.data_length())
+            };
+            if !force {
+                if let Err(err) = png.validate_structure() {
+                    return Err(format!("Refusing to save {}: {}. Pass --force to write it anyway.", path, err).into());
+                }
+            }
+            if args.dry_run {
+                println!("{}", dry_run_summary("remove", &chunk_type, bytes, &png));
+                return Ok(());
+            }
+            let interactive = std::io::stdin().is_terminal() && std::io::stdout().is_terminal();
+            let question = format!("Remove chunk {} from {}?", chunk_type, path);
+            if !confirm(&question, yes, no, interactive, std::io::stdin().lock(), &mut std::io::stdout())? {
+                println!("Left {} unchanged", path);
+                return Ok(());
+            }
+            save_png(&png, path, &args.output)?;
+            if all {
+                println!("Removed {} chunks", count);
+            }
+            else {
+                println!("Removed encoded message");
+            }
         },
 
-        cli::Commands::Decode { path, chunk_type } => {
-            let png = load_png(path).expect("Unable to read png.");
-            let chunk = png.chunk_by_type(&chunk_type);
-            if let Some(chunk) = chunk {
-                let message = chunk.data_as_string().expect("Error encoding data");
-                println!("{}", message);
+        cli::Commands::Replace { path, chunk_type, new_message, recompute_all_crcs } => {
+            let mut png = load_png(path.clone(), &args.input)?;
+            let bytes = new_message.len();
+            timing::timed("replace", || png.replace_chunk(&chunk_type, new_message.into_bytes()))?;
+            if recompute_all_crcs {
+                png.recompute_crcs();
             }
-            else{
-                println!("Nothing to decode");
+            if args.dry_run {
+                println!("{}", dry_run_summary("replace", &chunk_type, bytes, &png));
+                return Ok(());
             }
+            save_png(&png, path, &args.output)?;
+            println!("Replaced encoded message");
         },
-            
-        cli::Commands::Remove { path, chunk_type } => {
-            let mut png = load_png(path.clone()).expect("Unable to read png.");
-            png.remove_chunk(&chunk_type).expect("Error removing chunk.");
-            save_png(png, path).expect("Error saving output file.");
-            println!("Removed encoded message")
+
+        cli::Commands::Retype { path, old_type, new_type, fix_reserved } => {
+            let mut new_chunk_type = ChunkType::from_str(&new_type)?;
+            if fix_reserved && !new_chunk_type.is_reserved_bit_valid() {
+                new_chunk_type = new_chunk_type.with_valid_reserved_bit();
+                eprintln!("using {} instead of {}", new_chunk_type, new_type);
+            }
+            let new_type = new_chunk_type.to_string();
+            if !new_chunk_type.is_valid() {
+                return Err(format!("Invalid chunk type {}: the third letter must be uppercase", new_type).into());
+            }
+            let mut png = load_png(path.clone(), &args.input)?;
+            timing::timed("retype", || png.retype_chunk(&old_type, new_chunk_type))?;
+            if args.dry_run {
+                let bytes = png.chunk_by_type(&new_type).map_or(0, |chunk| chunk.data().len());
+                println!("{}", dry_run_summary("retype", &format!("{} -> {}", old_type, new_type), bytes, &png));
+                return Ok(());
+            }
+            save_png(&png, path, &args.output)?;
+            println!("Retyped {} to {}", old_type, new_type);
         },
 
-        cli::Commands::Print { path } => {
-            match load_png(path) {
-                Ok(png) => println!("{}", png.to_string()),
-                Err(_) => panic!("Unable to read png.")
+        cli::Commands::Reorder { path } => {
+            let mut png = load_png(path.clone(), &args.input)?;
+            let moved = png.move_ancillary_before_idat();
+            png.validate_structure().map_err(|err| format!("Refusing to save {}: {}", path, err))?;
+            if moved == 0 {
+                println!("Nothing to move");
+                return Ok(());
+            }
+            if args.dry_run {
+                println!("would move {} chunks before IDAT", moved);
+                return Ok(());
             }
+            save_png(&png, path, &args.output)?;
+            println!("Moved {} chunks before IDAT", moved);
         },
 
-    }
+        cli::Commands::Transfer { src, dst, chunk_type, output } => {
+            let source = load_png(src.clone(), &args.input)?;
+            let chunk = source.chunk_by_type(&chunk_type)
+                .ok_or_else(|| format!("{} has no {} chunk to transfer", src, chunk_type))?
+                .clone();
+            let mut png = load_png(dst.clone(), &args.input)?;
+            let bytes = chunk.data().len();
+            png.insert_before_iend(chunk)?;
+            if args.dry_run {
+                println!("{}", dry_run_summary("transfer", &chunk_type, bytes, &png));
+                return Ok(());
+            }
+            let output = output.unwrap_or(dst.clone());
+            let written = save_png_from(&png, &dst, output.clone(), &args.output)?;
+            println!("wrote {} bytes to {}", written, output);
+        },
 
-    Ok(())
+        cli::Commands::Strip { path, output, keep } => {
+            let mut png = load_png(path.clone(), &args.input)?;
+            let before = png.total_data_size();
+            let keep: Vec<&str> = keep.iter().map(String::as_str).collect();
+            let stripped = png.strip_ancillary_except(&keep);
+            if args.dry_run {
+                let bytes = before - png.total_data_size();
+                println!("{}", dry_run_report(&format!("strip {} chunks ({} bytes)", stripped, bytes), &png));
+                return Ok(());
+            }
+            let output = output.unwrap_or(path.clone());
+            let written = save_png_from(&png, &path, output.clone(), &args.output)?;
+            println!("stripped {} chunks; wrote {} bytes to {}", stripped, written, output);
+        },
 
+        cli::Commands::Merge { base, template, output, replace } => {
+            let mut png = load_png(base.clone(), &args.input)?;
+            let template_png = load_png(template.clone(), &args.input)?;
+            let on_collision = if replace { OnCollision::Replace } else { OnCollision::Keep };
+            let before = png.total_data_size();
+            let merged = png.merge_ancillary_from(&template_png, on_collision)?;
+            if args.dry_run {
+                let change = size_change(before, png.total_data_size());
+                println!("{}", dry_run_report(&format!("merge {} chunks from {} ({})", merged, template, change), &png));
+                return Ok(());
+            }
+            let output = output.unwrap_or(base.clone());
+            let written = save_png_from(&png, &base, output.clone(), &args.output)?;
+            println!("merged {} chunks from {}; wrote {} bytes to {}", merged, template, written, output);
+        },
+
+        cli::Commands::SetItxt { path, keyword, text, language, translated_keyword, compress } => {
+            let mut png = load_png(path.clone(), &args.input)?;
+            let bytes = text.len();
+            let text = InternationalText { keyword, compressed: compress, language, translated_keyword, text };
+            set_itxt(&mut png, &text)?;
+            if args.dry_run {
+                println!("{}", dry_run_summary("store", "iTXt", bytes, &png));
+                return Ok(());
+            }
+            save_png(&png, path, &args.output)?;
+            println!("Stored iTXt {}", text.keyword);
+        },
+
+        cli::Commands::GetItxt { path, keyword } => {
+            let png = load_png(path, &args.input)?;
+            if get_itxt(&png, keyword.as_deref(), &mut std::io::stdout())? == 0 {
+                return Err("Nothing to decode".into());
+            }
+        },
+
+        cli::Commands::SetZtxt { path, keyword, value } => {
+            let mut png = load_png(path.clone(), &args.input)?;
+            let bytes = value.len();
+            let text = CompressedText { keyword, text: value };
+            set_ztxt(&mut png, &text)?;
+            if args.dry_run {
+                println!("{}", dry_run_summary("store", "zTXt", bytes, &png));
+                return Ok(());
+            }
+            save_png(&png, path, &args.output)?;
+            println!("Stored zTXt {}", text.keyword);
+        },
+
+        cli::Commands::GetZtxt { path, keyword } => {
+            let png = load_png(path, &args.input)?;
+            if get_ztxt(&png, &keyword, &mut std::io::stdout())? == 0 {
+                return Err("Nothing to decode".into());
+            }
+        },
+
+        cli::Commands::MetaSet { path, key, value } => {
+            let mut png = load_png(path.clone(), &args.input)?;
+            meta_set(&mut png, &key, &value)?;
+            if args.dry_run {
+                println!("{}", dry_run_summary("store", META_CHUNK_TYPE, value.len(), &png));
+                return Ok(());
+            }
+            save_png(&png, path, &args.output)?;
+            println!("Set {}", key);
+        },
+
+        cli::Commands::MetaGet { path, key } => {
+            let png = load_png(path, &args.input)?;
+            match meta_get(&png, &key)? {
+                Some(value) => println!("{}", value),
+                None => return Err(format!("No metadata key {}", key).into())
+            }
+        },
+
+        cli::Commands::MetaList { path } => {
+            let png = load_png(path, &args.input)?;
+            for (key, value) in read_meta(&png)? {
+                println!("{}: {}", key, meta_value_string(&value));
+            }
+        },
+
+        cli::Commands::Batch { manifest, fail_fast } => {
+            batch::run(&manifest, fail_fast, &mut std::io::stdout())?;
+        },
+
+        cli::Commands::Shell { path } => {
+            let png = load_png(path.clone(), &args.input)?;
+            shell::run(png, path, std::io::stdin().lock(), &mut std::io::stdout())?;
+        },
+
+        cli::Commands::List { path, options } => {
+            let png = load_png(path, &args.input)?;
+            list(&png, &options, &mut std::io::stdout())?;
+        },
+
+        cli::Commands::Info { path } => {
+            let png = load_png(path.clone(), &args.input)?;
+            let header = ImageHeader::from_png(&png).map_err(|err| format!("{}: {}", path, err))?;
+            println!("{}", header);
+        },
+
+        cli::Commands::Verify { path, strict, warn_size } => {
+            let png = load_png(path, &InputOptions { repair_lengths: true, ..args.input })?;
+            for warning in size_warnings(&png, warn_size) {
+                eprintln!("{}", warning);
+            }
+            let issues = verify::verify(&png, strict);
+            if issues.is_empty() {
+                println!("No issues found ({} chunks, {} data bytes)", png.chunks().len(), png.total_data_size());
+            }
+            else {
+                for issue in issues.iter() {
+                    println!("{}", issue);
+                }
+                return Err(format!("{} issue(s) found", issues.len()).into());
+            }
+        },
+
+        cli::Commands::Extract { path, out_dir, types } => {
+            let png = load_png(path, &args.input)?;
+            let written = extract(&png, Path::new(&out_dir), &types)
+                .map_err(|err| format!("Unable to extract into {}: {}", out_dir, err))?;
+            println!("extracted {} chunks to {}", written.len(), out_dir);
+        },
+
+        cli::Commands::Recover { path, output } => {
+            let bytes = std::fs::read(&path).map_err(|err| format!("Unable to load png file {}: {}", path, err))?;
+            let mut recovery = Png::recover(&bytes).map_err(|err| format!("Unable to read png {}: {}", path, err))?;
+            for (offset, err) in recovery.problems.iter() {
+                println!("byte {}: {}", offset, err);
+            }
+            println!("recovered {} chunks", recovery.png.chunks().len());
+
+            if let Some(output) = output {
+                recovery.png.ensure_iend();
+                let written = save_png_from(&recovery.png, &path, output.clone(), &args.output)?;
+                println!("wrote {} bytes to {}", written, output);
+            }
+        },
+
+        cli::Commands::Diff { a, b } => {
+            let res = diff::diff(&load_png(a, &args.input)?, &load_png(b, &args.input)?);
+            if res.is_empty() {
+                println!("No differences");
+            }
+            else {
+                print!("{}", res);
+            }
+        },
+
+        #[cfg(feature = "fixtures")]
+        cli::Commands::GenFixture { width, height, color_type, output } => {
+            let png = fixtures::minimal_png(width, height, color_type);
+            let written = save_png(&png, output.clone(), &args.output)?;
+            println!("wrote {} bytes to {}", written, output);
+        },
+
+        cli::Commands::Print { path, options } => {
+            let png = load_png(path, &args.input)?;
+            for warning in size_warnings(&png, options.warn_size) {
+                eprintln!("{}", warning);
+            }
+            let palette = Palette::new(options.color, std::io::stdout().is_terminal());
+            print(&png, &options, &palette, &mut std::io::stdout())?;
+        },
+
+    }
+
+    Ok(())
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png_from_chunks(chunks: &[(&str, &[u8])]) -> Png {
+        let bytes: Vec<u8> = Png::STANDARD_HEADER
+            .iter()
+            .copied()
+            .chain(chunks.iter().flat_map(|(chunk_type, data)| {
+                Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec()).as_bytes()
+            }))
+            .collect();
+        Png::try_from(bytes.as_slice()).unwrap()
+    }
+
+    fn testing_png() -> Png {
+        png_from_chunks(&[("IHDR", &[0; 13]), ("IDAT", &[1, 2, 3]), ("IEND", &[])])
+    }
+
+    fn encode_options(args: &[&str]) -> EncodeOptions {
+        let args = ["pngme", "encode", "file.png", "ruSt", "message"].iter().chain(args.iter());
+        match Cli::try_parse_from(args).unwrap().command {
+            cli::Commands::Encode { options, .. } => options,
+            _ => unreachable!()
+        }
+    }
+
+    fn print_options(args: &[&str]) -> PrintOptions {
+        let args = ["pngme", "print", "file.png"].iter().chain(args.iter());
+        match Cli::try_parse_from(args).unwrap().command {
+            cli::Commands::Print { options, .. } => options,
+            _ => unreachable!()
+        }
+    }
+
+    fn print_to_string(png: &Png, args: &[&str]) -> String {
+        let options = print_options(args);
+        let mut out = Vec::new();
+        print(png, &options, &Palette::new(options.color, false), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_size_warnings() {
+        let big = vec![0; 70_000];
+        let png = png_from_chunks(&[("IHDR", &[0; 13]), ("ruSt", &big), ("IDAT", &[1, 2, 3]), ("IEND", &[])]);
+        assert_eq!(print_options(&[]).warn_size, 65536);
+        assert_eq!(size_warnings(&png, print_options(&[]).warn_size), ["warning: chunk 1: ruSt holds 70000 bytes, more than 65536"]);
+        assert!(size_warnings(&png, print_options(&["--warn-size", "100000"]).warn_size).is_empty());
+
+        let small = png_from_chunks(&[("IHDR", &[0; 13]), ("ruSt", b"small"), ("IDAT", &[1, 2, 3]), ("IEND", &[])]);
+        assert!(size_warnings(&small, verify::DEFAULT_WARN_SIZE).is_empty());
+    }
+
+    #[test]
+    fn test_print_matches_display() {
+        let png = testing_png();
+        assert_eq!(print_to_string(&png, &[]), format!("{}\n", png));
+    }
+
+    #[test]
+    fn test_print_color() {
+        let png = testing_png();
+        assert!(!print_to_string(&png, &["--color", "never"]).contains('\x1b'));
+
+        let colored = print_to_string(&png, &["--color", "always"]);
+        assert!(colored.contains("Type: \x1b[31mIHDR\x1b[0m"));
+        assert!(colored.contains(&format!("Crc: \x1b[2m{}\x1b[0m", png.chunks()[0].crc())));
+    }
+
+    #[test]
+    fn test_print_stats() {
+        let png = png_from_chunks(&[("IHDR", &[0; 13]), ("gAMA", &[0; 4]), ("IDAT", &[1]), ("ruSt", b"hi"), ("IEND", &[])]);
+        let output = print_to_string(&png, &["--stats"]);
+        assert_eq!(output, "5 chunks\n\
+            critical:          3\n\
+            ancillary:         2\n\
+            public:            4\n\
+            private:           1\n\
+            safe to copy:      1\n\
+            private critical:  0\n");
+        assert!(Cli::try_parse_from(["pngme", "print", "file.png", "--stats", "--breakdown"]).is_err());
+    }
+
+    #[test]
+    fn test_print_breakdown() {
+        let png = png_from_chunks(&[("IHDR", &[0; 13]), ("IDAT", &[1; 2000]), ("ruSt", &[2; 500]), ("IEND", &[])]);
+        let output = print_to_string(&png, &["--breakdown"]);
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines[0], format!("{} bytes in total", png.as_bytes().len()));
+        assert!(lines[1].starts_with("IDAT        2012 bytes"));
+        assert!(lines[2].starts_with("ruSt         512 bytes"));
+        let total: f64 = lines[1..].iter()
+            .map(|line| line.trim_end_matches('%').rsplit(' ').next().unwrap().parse::<f64>().unwrap())
+            .sum();
+        assert!((total - 100.0).abs() < 0.5, "{}", total);
+    }
+
+    #[test]
+    fn test_print_tail() {
+        let png = png_from_chunks(&[
+            ("IHDR", &[0; 13]),
+            ("IDAT", &[1, 2, 3]),
+            ("ruSt", b"first"),
+            ("ruSt", b"second"),
+            ("IEND", &[]),
+        ]);
+
+        let output = print_to_string(&png, &["--tail", "2"]);
+
+        assert_eq!(output.matches("Chunk {").count(), 2);
+        let rust = output.find("Type: ruSt").unwrap();
+        let iend = output.find("Type: IEND").unwrap();
+        assert!(rust < iend);
+        assert!(!output.contains("IHDR"));
+    }
+
+    #[test]
+    fn test_print_hexdump_max_bytes() {
+        let data = [0x41; 300];
+        let png = png_from_chunks(&[("IHDR", &[0; 13]), ("ruSt", &data), ("IEND", &[])]);
+
+        let output = print_to_string(&png, &["--hexdump"]);
+        assert!(output.contains("... 44 more bytes"));
+
+        let output = print_to_string(&png, &["--hexdump", "--max-bytes", "16"]);
+        assert!(output.contains("... 284 more bytes"));
+    }
+
+    #[test]
+    fn test_print_tail_larger_than_png() {
+        let png = testing_png();
+        let output = print_to_string(&png, &["--tail", "10"]);
+        assert_eq!(output.matches("Chunk {").count(), 3);
+    }
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("pngme-{}-{}", std::process::id(), name))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn test_save_png_reports_bytes_written() {
+        let png = testing_png();
+        let expected = png.as_bytes().len();
+        let path = temp_path("save_png_reports_bytes_written.png");
+
+        let written = save_png(&png, path.clone(), &OutputOptions::default()).unwrap();
+
+        assert_eq!(written, expected);
+        assert_eq!(std::fs::metadata(&path).unwrap().len() as usize, expected);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    fn list_to_string(png: &Png, args: &[&str]) -> String {
+        let args = ["pngme", "list", "file.png"].iter().chain(args.iter());
+        let options = match Cli::try_parse_from(args).unwrap().command {
+            cli::Commands::List { options, .. } => options,
+            _ => unreachable!()
+        };
+        let mut out = Vec::new();
+        list(png, &options, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_list() {
+        let png = testing_png();
+        let output = list_to_string(&png, &[]);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("    0  IHDR          13 bytes  crc "));
+        assert!(lines[2].starts_with("    2  IEND           0 bytes  crc ae426082"));
+        assert!(lines[0].ends_with("  image header"));
+    }
+
+    #[test]
+    fn test_type_filter() {
+        let png = png_from_chunks(&[
+            ("IHDR", &[0; 13]),
+            ("tEXt", b"a\0b"),
+            ("IDAT", &[1]),
+            ("IDAT", &[2, 2]),
+            ("tIME", &[0; 7]),
+            ("IEND", &[]),
+        ]);
+
+        let output = list_to_string(&png, &["--type", "IDAT"]);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("    2  IDAT"));
+        assert!(lines[1].starts_with("    3  IDAT"));
+
+        let output = list_to_string(&png, &["--type", "t*", "--type", "IEND"]);
+        assert_eq!(output.lines().count(), 3);
+
+        let output = print_to_string(&png, &["--type", "IDAT"]);
+        assert_eq!(output.matches("Chunk {").count(), 2);
+        assert_eq!(output.matches("Type: IDAT").count(), 2);
+    }
+
+    #[test]
+    fn test_list_group_by_type() {
+        let png = png_from_chunks(&[
+            ("IHDR", &[0; 13]),
+            ("IDAT", &[1]),
+            ("IDAT", &[2, 2]),
+            ("IDAT", &[3, 3, 3]),
+            ("ruSt", b"message"),
+            ("meTa", b"metadata"),
+            ("IEND", &[]),
+        ]);
+
+        let output = list_to_string(&png, &["--group-by-type"]);
+        let headers: Vec<&str> = output.lines().filter(|line| !line.starts_with(' ')).collect();
+        assert_eq!(headers, ["IDAT (3)", "IEND (1)", "IHDR (1)", "meTa (1)", "ruSt (1)"]);
+
+        let idat_section: Vec<&str> = output.lines().skip(1).take(3).collect();
+        assert!(idat_section[0].starts_with("    1  IDAT           1 bytes"));
+        assert!(idat_section[1].starts_with("    2  IDAT           2 bytes"));
+        assert!(idat_section[2].starts_with("    3  IDAT           3 bytes"));
+    }
+
+    /// Splits one CSV record, honouring quoted fields.
+    fn parse_csv_row(line: &str) -> Vec<String> {
+        let mut fields = vec!(String::new());
+        let mut quoted = false;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted && chars.peek() == Some(&'"') => { chars.next(); fields.last_mut().unwrap().push('"'); },
+                '"' => quoted = !quoted,
+                ',' if !quoted => fields.push(String::new()),
+                c => fields.last_mut().unwrap().push(c),
+            }
+        }
+        fields
+    }
+
+    #[test]
+    fn test_list_csv() {
+        let png = png_from_chunks(&[("IHDR", &[0; 13]), ("ruSt", b"message"), ("IEND", &[])]);
+        let output = list_to_string(&png, &["--format", "csv"]);
+        let rows: Vec<Vec<String>> = output.lines().map(parse_csv_row).collect();
+
+        assert_eq!(rows.len(), 4);
+        assert!(rows.iter().all(|row| row.len() == 8));
+        assert_eq!(rows[0], ["index", "type", "length", "crc", "critical", "public", "safe_to_copy", "known"]);
+        assert_eq!(rows[2][..3], ["1", "ruSt", "7"]);
+        assert_eq!(rows[2][4..], ["false", "false", "true", "false"]);
+        assert_eq!(rows[1][4], "true");
+
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(parse_csv_row(&csv_field("a,\"b\"")), ["a,\"b\""]);
+    }
+
+    #[test]
+    fn test_list_json() {
+        let png = png_from_chunks(&[("IHDR", &[0; 13]), ("ruSt", b"message"), ("IEND", &[])]);
+        let output = json::parse(&list_to_string(&png, &["--format", "json"])).unwrap();
+        let chunks = output.as_array().unwrap();
+
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[1].get("type").and_then(Json::as_str), Some("ruSt"));
+        assert_eq!(chunks[1].get("length").and_then(Json::as_f64), Some(7.0));
+        assert_eq!(chunks[0].get("critical").and_then(Json::as_bool), Some(true));
+        assert_eq!(chunks[0].get("known").and_then(Json::as_bool), Some(true));
+        assert_eq!(chunks[1].get("known").and_then(Json::as_bool), Some(false));
+
+        let compact = list_to_string(&png, &["--format", "json"]);
+        let pretty = list_to_string(&png, &["--format", "json", "--pretty"]);
+        assert_eq!(compact.lines().count(), 1);
+        assert!(pretty.lines().count() > 3);
+        assert_eq!(json::parse(&compact).unwrap(), json::parse(&pretty).unwrap());
+    }
+
+    #[test]
+    fn test_list_paging() {
+        let png = png_from_chunks(&[("IHDR", &[0; 13]), ("ruSt", b"one"), ("meTa", b"two"), ("IDAT", &[1]), ("IEND", &[])]);
+
+        let output = list_to_string(&png, &["--skip", "2", "--limit", "1"]);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("    2  meTa"));
+        assert_eq!(lines[1], "showing 2..3 of 5");
+
+        assert_eq!(list_to_string(&png, &["--skip", "10"]), "showing 5..5 of 5\n");
+        assert_eq!(list_to_string(&png, &["--limit", "100"]).lines().count(), 6);
+        assert!(!list_to_string(&png, &[]).contains("showing"));
+    }
+
+    #[test]
+    fn test_list_head_and_tail() {
+        let png = png_from_chunks(&[("IHDR", &[0; 13]), ("ruSt", b"one"), ("IDAT", &[1]), ("IDAT", &[2]), ("IDAT", &[3]), ("IEND", &[])]);
+
+        let output = list_to_string(&png, &["--head", "1", "--tail", "1"]);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("    0  IHDR"));
+        assert_eq!(lines[1], "... 4 chunks omitted ...");
+        assert!(lines[2].starts_with("    5  IEND"));
+
+        let head: Vec<String> = list_to_string(&png, &["--head", "2"]).lines().map(String::from).collect();
+        assert_eq!(head.len(), 3);
+        assert_eq!(head[2], "... 4 chunks omitted ...");
+        assert!(list_to_string(&png, &["--tail", "1"]).starts_with("... 5 chunks omitted ...\n    5  IEND"));
+        assert_eq!(list_to_string(&png, &["--head", "3", "--tail", "3"]), list_to_string(&png, &[]));
+        assert_eq!(list_to_string(&png, &["--head", "1", "--tail", "1", "--format", "csv"]).lines().count(), 3);
+    }
+
+    #[test]
+    fn test_hash() {
+        let png = png_from_chunks(&[("IHDR", &[0; 13]), ("ruSt", b"same"), ("meTa", b"same"), ("ruSt", b"different"), ("IEND", &[])]);
+        let output = list_to_string(&png, &["--hash"]);
+        let hashes: Vec<&str> = output.lines()
+            .map(|line| line.split("sha256 ").nth(1).unwrap().split(' ').next().unwrap())
+            .collect();
+
+        assert_eq!(hashes[1], hashes[2]);
+        assert_ne!(hashes[1], hashes[3]);
+        assert_eq!(hashes[1], sha256::sha256_hex(b"same"));
+        assert!(!list_to_string(&png, &[]).contains("sha256"));
+
+        let output = print_to_string(&png, &["--hash"]);
+        assert_eq!(output.matches(&format!("Sha256: {}", hashes[1])).count(), 2);
+    }
+
+    #[test]
+    fn test_sort_is_display_only() {
+        let png = png_from_chunks(&[
+            ("IHDR", &[0; 13]),
+            ("ruSt", b"message"),
+            ("IDAT", &[1]),
+            ("meTa", b"metadata"),
+            ("IEND", &[]),
+        ]);
+        let original = png.as_bytes();
+
+        let output = list_to_string(&png, &["--sort"]);
+        let types: Vec<&str> = output.lines().map(|line| &line[7..11]).collect();
+        assert_eq!(types, ["IDAT", "IEND", "IHDR", "meTa", "ruSt"]);
+        assert!(output.lines().next().unwrap().starts_with("    2  IDAT"));
+
+        let output = print_to_string(&png, &["--sort"]);
+        let idat = output.find("Type: IDAT").unwrap();
+        let ihdr = output.find("Type: IHDR").unwrap();
+        let rust = output.find("Type: ruSt").unwrap();
+        assert!(idat < ihdr && ihdr < rust);
+
+        let path = temp_path("sort_is_display_only.png");
+        save_png(&png, path.clone(), &OutputOptions::default()).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), original);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    fn decode_options(args: &[&str]) -> DecodeOptions {
+        let args = ["pngme", "decode", "file.png"].iter().chain(args.iter());
+        match Cli::try_parse_from(args).unwrap().command {
+            cli::Commands::Decode { options, .. } => options,
+            _ => unreachable!()
+        }
+    }
+
+    fn decode_to_string(png: &Png, chunk_type: &str) -> (usize, String) {
+        let mut out = Vec::new();
+        let found = decode(png, chunk_type, &DecodeOptions::default(), &mut out).unwrap();
+        (found, String::from_utf8(out).unwrap())
+    }
+
+    #[cfg(feature = "clipboard")]
+    #[test]
+    fn test_clipboard_falls_back_to_printing() {
+        let (_, message) = decode_to_string(&png_from_chunks(&[("IHDR", &[0; 13]), ("msAa", b"secret"), ("IEND", &[])]), "msAa");
+        let missing: &[clipboard::Tool] = &[("pngme-test-no-such-clipboard-tool", &[])];
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        copy_or_print(message.as_bytes(), |text| clipboard::copy_with(text, missing), &mut out, &mut err).unwrap();
+        assert_eq!(out, b"secret\n");
+        assert_eq!(
+            String::from_utf8(err).unwrap(),
+            "warning: no clipboard available (tried pngme-test-no-such-clipboard-tool); printing instead\n"
+        );
+
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let mut copied = String::new();
+        copy_or_print(message.as_bytes(), |text| { copied = text.to_string(); Ok(()) }, &mut out, &mut err).unwrap();
+        assert_eq!(copied, "secret");
+        assert!(out.is_empty());
+    }
+
+    /// Counts the bytes read through it, to check how far a streaming parser got.
+    struct CountingReader<'a> {
+        bytes: &'a [u8],
+        read: std::rc::Rc<std::cell::Cell<usize>>,
+    }
+
+    impl Read for CountingReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.bytes.read(buf)?;
+            self.read.set(self.read.get() + n);
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_decode_stream_first_stops_early() {
+        let png = png_from_chunks(&[("IHDR", &[0; 13]), ("ruSt", b"early"), ("IDAT", &[7; 100_000]), ("ruSt", b"late"), ("IEND", &[])]);
+        let bytes = png.as_bytes();
+        let run_stream = |args: &[&str]| {
+            let read = std::rc::Rc::new(std::cell::Cell::new(0));
+            let reader = CountingReader { bytes: &bytes, read: read.clone() };
+            let mut out = Vec::new();
+            let found = decode_stream(reader, "ruSt", &decode_options(args), &mut out).unwrap();
+            (found, String::from_utf8(out).unwrap(), read.get())
+        };
+
+        let (found, out, read) = run_stream(&["ruSt", "--stream", "--first"]);
+        assert_eq!((found, out.as_str()), (1, "early\n"));
+        assert!(read < 1000, "read {} bytes", read);
+
+        let (found, out, read) = run_stream(&["ruSt", "--stream"]);
+        assert_eq!((found, out.as_str()), (2, "early\nlate\n"));
+        assert_eq!(read, bytes.len());
+
+        let mut out = Vec::new();
+        assert!(decode_stream(&bytes[..bytes.len() - 5], "ruSt", &decode_options(&["ruSt", "--stream"]), &mut out).is_err());
+        assert_eq!(out, b"early\nlate\n");
+    }
+
+    #[test]
+    fn test_watch_decodes_again_after_change() {
+        let path = temp_path("watch_decode.png");
+        std::fs::write(&path, png_from_chunks(&[("IHDR", &[0; 13]), ("ruSt", b"one"), ("IEND", &[])]).as_bytes()).unwrap();
+        let decode_file = |path: &str| {
+            let png = load_png(path.to_string(), &InputOptions::default()).unwrap();
+            decode_to_string(&png, "ruSt").1
+        };
+        assert_eq!(decode_file(&path), "one\n");
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let watched = path.clone();
+        std::thread::spawn(move || {
+            watch::watch(Path::new(&watched), std::time::Duration::from_millis(20), std::time::Duration::from_millis(50), || {
+                sender.send(decode_file(&watched)).unwrap();
+                std::ops::ControlFlow::Break(())
+            });
+        });
+        std::thread::sleep(std::time::Duration::from_millis(60));
+        std::fs::write(&path, png_from_chunks(&[("IHDR", &[0; 13]), ("ruSt", b"two"), ("IEND", &[])]).as_bytes()).unwrap();
+
+        assert_eq!(receiver.recv_timeout(std::time::Duration::from_secs(5)).unwrap(), "two\n");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_decode_recurse() {
+        let inner = png_from_chunks(&[("IHDR", &[0; 13]), ("ruSt", b"deep"), ("IEND", &[])]);
+        let middle = png_from_chunks(&[("IHDR", &[0; 13]), ("ruSt", &inner.as_bytes()), ("ruSt", b"middle"), ("IEND", &[])]);
+        let outer = png_from_chunks(&[("IHDR", &[0; 13]), ("ruSt", b"top"), ("ruSt", &middle.as_bytes()), ("IEND", &[])]);
+
+        let decode_with = |args: &[&str]| {
+            let mut out = Vec::new();
+            let found = decode(&outer, "ruSt", &decode_options(args), &mut out).unwrap();
+            (found, String::from_utf8(out).unwrap())
+        };
+        assert_eq!(decode_with(&["ruSt", "--recurse"]), (3, String::from("1:ruSt: top\n2:ruSt/1:ruSt/1:ruSt: deep\n2:ruSt/2:ruSt: middle\n")));
+        assert_eq!(decode_with(&["ruSt", "--recurse", "--max-depth", "1"]), (2, String::from("1:ruSt: top\n2:ruSt/2:ruSt: middle\n")));
+        assert_eq!(decode_with(&["ruSt", "--recurse", "--max-depth", "0"]), (1, String::from("1:ruSt: top\n")));
+    }
+
+    #[test]
+    fn test_decode_exact() {
+        let png = png_from_chunks(&[("IHDR", &[0; 13]), ("msAa", b"one"), ("msAa", b"two"), ("IEND", &[])]);
+        assert_eq!(decode_to_string(&png, "msAa"), (1, String::from("one\n")));
+        assert_eq!(decode_to_string(&png, "msAb"), (0, String::new()));
+    }
+
+    #[test]
+    fn test_decode_pattern() {
+        let png = png_from_chunks(&[
+            ("IHDR", &[0; 13]),
+            ("msAa", b"one"),
+            ("ruSt", b"other"),
+            ("msBb", b"two"),
+            ("IEND", &[]),
+        ]);
+        assert_eq!(decode_to_string(&png, "ms*"), (2, String::from("msAa: one\nmsBb: two\n")));
+        assert_eq!(decode_to_string(&png, "??Bb"), (1, String::from("msBb: two\n")));
+        assert_eq!(decode_to_string(&png, "zz*"), (0, String::new()));
+    }
+
+    #[test]
+    fn test_decode_ignore_case() {
+        let png = png_from_chunks(&[("IHDR", &[0; 13]), ("ruSt", b"hidden"), ("IEND", &[])]);
+
+        let mut out = Vec::new();
+        assert_eq!(decode(&png, "Rust", &decode_options(&["Rust"]), &mut out).unwrap(), 0);
+
+        let found = decode(&png, "Rust", &decode_options(&["Rust", "--ignore-case"]), &mut out).unwrap();
+        assert_eq!(found, 1);
+        assert_eq!(String::from_utf8(out).unwrap(), "hidden\n");
+    }
+
+    fn decode_at_to_string(png: &Png, index: usize, args: &[&str]) -> Result<String> {
+        let args = ["pngme", "decode-at", "file.png", "0"].iter().chain(args.iter());
+        let options = match Cli::try_parse_from(args).unwrap().command {
+            cli::Commands::DecodeAt { options, .. } => options,
+            _ => unreachable!()
+        };
+        let mut out = Vec::new();
+        decode_at(png, index, &options, &mut out)?;
+        Ok(String::from_utf8_lossy(&out).into_owned())
+    }
+
+    #[test]
+    fn test_decode_at() {
+        let png = png_from_chunks(&[("IHDR", &[0; 13]), ("ruSt", b"first"), ("ruSt", b"second\xff"), ("IEND", &[])]);
+        assert_eq!(decode_at_to_string(&png, 1, &[]).unwrap(), "first\n");
+        assert!(decode_at_to_string(&png, 2, &[]).is_err());
+        assert_eq!(decode_at_to_string(&png, 2, &["--lossy"]).unwrap(), "second\u{fffd}\n");
+        assert_eq!(decode_at_to_string(&png, 1, &["--raw"]).unwrap(), "first");
+    }
+
+    #[test]
+    fn test_decode_at_out_of_range() {
+        let png = testing_png();
+        let err = decode_at_to_string(&png, 3, &[]).unwrap_err();
+        assert!(matches!(err.downcast_ref::<PngError>(), Some(PngError::IndexOutOfBounds(3))));
+    }
+
+    #[test]
+    fn test_decode_all() {
+        let png = png_from_chunks(&[
+            ("IHDR", &[0; 13]),
+            ("IDAT", &[0xff, 0xfe, 0x00]),
+            ("ruSt", b"first message"),
+            ("tEXt", b"Comment\0second message"),
+            ("BiNr", &[0xc3, 0x28]),
+            ("IEND", &[]),
+        ]);
+
+        let mut out = Vec::new();
+        let count = decode_all(&png, &decode_options(&["--all"]), &mut out).unwrap();
+
+        assert_eq!(count, 2);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "ruSt: first message\ntEXt: Comment\0second message\n"
+        );
+    }
+
+    #[test]
+    fn test_decode_all_include_critical() {
+        let png = png_from_chunks(&[("IHDR", &[0; 13]), ("CrIt", b"critical"), ("ruSt", b"text"), ("IEND", &[])]);
+
+        let mut out = Vec::new();
+        assert_eq!(decode_all(&png, &decode_options(&["--all"]), &mut out).unwrap(), 1);
+
+        let mut out = Vec::new();
+        assert_eq!(decode_all(&png, &decode_options(&["--all", "--include-critical"]), &mut out).unwrap(), 3);
+        assert!(String::from_utf8(out).unwrap().contains("CrIt: critical"));
+    }
+
+    #[test]
+    fn test_decode_requires_chunk_type_or_all() {
+        assert!(Cli::try_parse_from(["pngme", "decode", "file.png"]).is_err());
+        assert!(Cli::try_parse_from(["pngme", "decode", "file.png", "--all"]).is_ok());
+        assert!(Cli::try_parse_from(["pngme", "decode", "file.png", "ruSt"]).is_ok());
+    }
+
+    #[test]
+    fn test_encode_ancillary() {
+        let mut png = testing_png();
+        encode(&mut png, "ruSt", b"secret", &encode_options(&[])).unwrap();
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data_as_string().unwrap(), "secret");
+    }
+
+    #[test]
+    fn test_encode_color_chunk_before_idat() {
+        let mut png = testing_png();
+        encode(&mut png, "sRGB", b"\0", &encode_options(&[])).unwrap();
+        let types: Vec<String> = png.chunks().iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "sRGB", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_encode_split_round_trip() {
+        let mut png = testing_png();
+        let message = "A long message that gets split into four";
+        encode(&mut png, "ruSt", message.as_bytes(), &encode_options(&["--split", "10"])).unwrap();
+        assert_eq!(png.chunks_by_type("ruSt").len(), 4);
+
+        let mut out = Vec::new();
+        assert_eq!(decode(&png, "ruSt", &decode_options(&["ruSt", "--split"]), &mut out).unwrap(), 4);
+        assert_eq!(String::from_utf8(out).unwrap(), format!("{}\n", message));
+    }
+
+    #[test]
+    fn test_encode_decode_encodings() {
+        for encoding in ["raw", "base64", "hex"] {
+            let mut png = testing_png();
+            let message = "Grüße, 世界! 🦀";
+            encode(&mut png, "ruSt", message.as_bytes(), &encode_options(&["--encoding", encoding])).unwrap();
+
+            let mut out = Vec::new();
+            decode(&png, "ruSt", &decode_options(&["ruSt", "--encoding", encoding]), &mut out).unwrap();
+            assert_eq!(String::from_utf8(out).unwrap(), format!("{}\n", message));
+        }
+    }
+
+    #[test]
+    fn test_encode_base64_stores_encoded_bytes() {
+        let mut png = testing_png();
+        encode(&mut png, "ruSt", b"foo", &encode_options(&["--encoding", "base64"])).unwrap();
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), b"Zm9v");
+    }
+
+    #[test]
+    fn test_decode_malformed_encoding() {
+        let png = png_from_chunks(&[("IHDR", &[0; 13]), ("ruSt", b"not hex!"), ("IEND", &[])]);
+        let mut out = Vec::new();
+        assert!(decode(&png, "ruSt", &decode_options(&["ruSt", "--encoding", "hex"]), &mut out).is_err());
+    }
+
+    #[test]
+    fn test_encode_critical_refused() {
+        let mut png = testing_png();
+        assert!(encode(&mut png, "IEND", b"secret", &encode_options(&[])).is_err());
+        assert!(encode(&mut png, "RuSt", b"secret", &encode_options(&[])).is_err());
+        assert_eq!(png.chunks().len(), 3);
+    }
+
+    #[test]
+    fn test_encode_critical_forced() {
+        let mut png = testing_png();
+        encode(&mut png, "IEND", b"secret", &encode_options(&["--force"])).unwrap();
+        assert_eq!(png.chunks().len(), 4);
+    }
+
+    #[test]
+    fn test_dry_run_encode_leaves_files_untouched() {
+        let path = temp_path("dry_run_input.png");
+        let output = temp_path("dry_run_output.png");
+        let original = testing_png().as_bytes();
+        std::fs::write(&path, &original).unwrap();
+
+        let args = Cli::try_parse_from(["pngme", "--dry-run", "encode", &path, "ruSt", "hello", &output]).unwrap();
+        run(args).unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), original);
+        assert!(!std::path::Path::new(&output).exists());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_dry_run_report() {
+        let png = testing_png();
+        let size = png.as_bytes().len();
+        assert_eq!(dry_run_summary("remove", "ruSt", 5, &png), format!("would remove chunk ruSt (5 bytes), new file size {} bytes", size));
+        assert_eq!(dry_run_report("strip 3 chunks (40 bytes)", &png), format!("would strip 3 chunks (40 bytes), new file size {} bytes", size));
+
+        assert_eq!(size_change(10, 52), "+42 bytes");
+        assert_eq!(size_change(52, 10), "-42 bytes");
+        assert_eq!(size_change(7, 7), "+0 bytes");
+    }
+
+    #[test]
+    fn test_remove_only_idat_rejected() {
+        let path = temp_path("remove_idat.png");
+        let original = testing_png().as_bytes();
+        std::fs::write(&path, &original).unwrap();
+
+        let args = Cli::try_parse_from(["pngme", "remove", &path, "IDAT"]).unwrap();
+        assert!(run(args).is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), original);
+
+        let args = Cli::try_parse_from(["pngme", "remove", &path, "IDAT", "--force", "--yes"]).unwrap();
+        run(args).unwrap();
+        let saved = Png::try_from(std::fs::read(&path).unwrap().as_slice()).unwrap();
+        assert!(saved.chunk_by_type("IDAT").is_none());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_remove_custom_chunk_allowed() {
+        let path = temp_path("remove_custom.png");
+        let mut png = testing_png();
+        encode(&mut png, "ruSt", b"hidden", &encode_options(&[])).unwrap();
+        std::fs::write(&path, png.as_bytes()).unwrap();
+
+        let args = Cli::try_parse_from(["pngme", "remove", &path, "ruSt", "--yes"]).unwrap();
+        run(args).unwrap();
+        let saved = Png::try_from(std::fs::read(&path).unwrap().as_slice()).unwrap();
+        assert!(saved.chunk_by_type("ruSt").is_none());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_confirm() {
+        let mut out = Vec::new();
+        assert!(!confirm("Remove chunk ruSt from foo.png?", false, false, true, &b"n\n"[..], &mut out).unwrap());
+        assert_eq!(String::from_utf8(out).unwrap(), "Remove chunk ruSt from foo.png? [y/N] ");
+
+        assert!(confirm("?", false, false, true, &b"Y\n"[..], &mut Vec::new()).unwrap());
+        assert!(!confirm("?", false, false, true, &b"\n"[..], &mut Vec::new()).unwrap());
+        assert!(confirm("?", false, false, false, &b""[..], &mut Vec::new()).unwrap());
+        assert!(confirm("?", true, false, true, &b"n\n"[..], &mut Vec::new()).unwrap());
+        assert!(!confirm("?", false, true, false, &b""[..], &mut Vec::new()).unwrap());
+    }
+
+    #[test]
+    fn test_remove_declined_leaves_file_unchanged() {
+        let path = temp_path("remove_declined.png");
+        let mut png = testing_png();
+        encode(&mut png, "ruSt", b"hidden", &encode_options(&[])).unwrap();
+        std::fs::write(&path, png.as_bytes()).unwrap();
+
+        run(Cli::try_parse_from(["pngme", "remove", &path, "ruSt", "--no"]).unwrap()).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), png.as_bytes());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_merge_template_chunks() {
+        let (base, template, output) = (temp_path("merge_base.png"), temp_path("merge_template.png"), temp_path("merge_out.png"));
+        std::fs::write(&base, testing_png().as_bytes()).unwrap();
+        let stamp = png_from_chunks(&[("IHDR", &[7; 13]), ("ruSt", b"one"), ("stMp", b"two"), ("IDAT", &[9]), ("IEND", &[])]);
+        std::fs::write(&template, stamp.as_bytes()).unwrap();
+
+        run(Cli::try_parse_from(["pngme", "merge", &base, &template, "--output", &output]).unwrap()).unwrap();
+        let merged = load_png(output.clone(), &InputOptions::default()).unwrap();
+        let types: Vec<String> = merged.iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "IDAT", "ruSt", "stMp", "IEND"]);
+        assert_eq!(merged.chunk_by_type("IDAT").unwrap().data(), &[1, 2, 3]);
+        assert_eq!(merged.chunk_by_type("stMp").unwrap().data(), b"two");
+
+        for path in [base, template, output] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_reorder_moves_text_before_idat() {
+        let path = temp_path("reorder.png");
+        let png = png_from_chunks(&[("IHDR", &[0; 13]), ("IDAT", &[1, 2, 3]), ("tEXt", b"Comment\0late"), ("IEND", &[])]);
+        std::fs::write(&path, png.as_bytes()).unwrap();
+
+        run(Cli::try_parse_from(["pngme", "reorder", &path]).unwrap()).unwrap();
+        let saved = load_png(path.clone(), &InputOptions::default()).unwrap();
+        let types: Vec<String> = saved.iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "tEXt", "IDAT", "IEND"]);
+        assert!(saved.validate_structure().is_ok());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_encode_to_several_outputs() {
+        let path = temp_path("fan_out.png");
+        let first = temp_path("fan_out_1.png");
+        let second = temp_path("fan_out_2.png");
+        std::fs::write(&path, testing_png().as_bytes()).unwrap();
+
+        run(Cli::try_parse_from(["pngme", "encode", &path, "ruSt", "watermark", "--output", &first, "--output", &second]).unwrap()).unwrap();
+        let written = std::fs::read(&first).unwrap();
+        assert_eq!(written, std::fs::read(&second).unwrap());
+        let png = Png::try_from(written.as_slice()).unwrap();
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data_as_string().unwrap(), "watermark");
+
+        // an unwritable target is reported without stopping the others
+        std::fs::remove_file(&second).unwrap();
+        let missing = temp_path("no_such_dir/fan_out.png");
+        let err = run(Cli::try_parse_from(["pngme", "encode", &path, "ruSt", "again", "--output", &missing, "--output", &second]).unwrap()).err().unwrap();
+        assert_eq!(err.to_string(), "1 of 2 outputs could not be written");
+        assert!(Path::new(&second).exists());
+
+        assert!(Cli::try_parse_from(["pngme", "encode", &path, "ruSt", "msg", "out.png", "--output", &first]).is_err());
+        for file in [path, first, second] {
+            std::fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_strip_keeps_only_critical_chunks() {
+        let path = temp_path("strip.png");
+        let output = temp_path("strip_out.png");
+        let png = png_from_chunks(&[("IHDR", &[0; 13]), ("gAMA", &[0, 0, 177, 143]), ("IDAT", &[1, 2, 3]), ("ruSt", b"hidden"), ("IEND", &[])]);
+        std::fs::write(&path, png.as_bytes()).unwrap();
+
+        run(Cli::try_parse_from(["pngme", "strip", &path, "--output", &output]).unwrap()).unwrap();
+        let saved = load_png(output.clone(), &InputOptions::default()).unwrap();
+        let types: Vec<String> = saved.iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "IDAT", "IEND"]);
+        assert!(saved.validate_structure().is_ok());
+
+        run(Cli::try_parse_from(["pngme", "strip", &path, "--keep", "gAMA"]).unwrap()).unwrap();
+        let saved = load_png(path.clone(), &InputOptions::default()).unwrap();
+        let types: Vec<String> = saved.iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "gAMA", "IDAT", "IEND"]);
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_verify_reports_tampered_length() {
+        let path = temp_path("verify_length.png");
+        let mut bytes = testing_png().as_bytes();
+        // the length field of IDAT, after the signature and the 25-byte IHDR chunk
+        bytes[8 + 25 + 3] = 1;
+        std::fs::write(&path, &bytes).unwrap();
+
+        assert!(run(Cli::try_parse_from(["pngme", "print", &path]).unwrap()).is_err());
+        let err = run(Cli::try_parse_from(["pngme", "verify", &path]).unwrap()).err().unwrap();
+        assert_eq!(err.to_string(), "1 issue(s) found");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_info_requires_valid_ihdr() {
+        let path = temp_path("info.png");
+        std::fs::write(&path, testing_png().as_bytes()).unwrap();
+        run(Cli::try_parse_from(["pngme", "info", &path]).unwrap()).unwrap();
+
+        let png = png_from_chunks(&[("IHDR", &[0; 12]), ("IDAT", &[1, 2, 3]), ("IEND", &[])]);
+        std::fs::write(&path, png.as_bytes()).unwrap();
+        let err = run(Cli::try_parse_from(["pngme", "info", &path]).unwrap()).err().unwrap();
+        assert_eq!(err.to_string(), format!("{}: IHDR data must be exactly 13 bytes, found 12", path));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_transfer() {
+        let src = temp_path("transfer_src.png");
+        let dst = temp_path("transfer_dst.png");
+        let output = temp_path("transfer_out.png");
+        let mut png = testing_png();
+        encode(&mut png, "ruSt", b"watermark", &encode_options(&[])).unwrap();
+        std::fs::write(&src, png.as_bytes()).unwrap();
+        std::fs::write(&dst, testing_png().as_bytes()).unwrap();
+
+        let args = Cli::try_parse_from(["pngme", "transfer", &src, &dst, "ruSt", "--output", &output]).unwrap();
+        run(args).unwrap();
+
+        let transferred = Png::try_from(std::fs::read(&output).unwrap().as_slice()).unwrap();
+        assert_eq!(decode_to_string(&transferred, "ruSt"), (1, String::from("watermark\n")));
+        assert_eq!(transferred.chunks().last().unwrap().chunk_type().to_string(), "IEND");
+        assert_eq!(std::fs::read(&dst).unwrap(), testing_png().as_bytes());
+
+        let args = Cli::try_parse_from(["pngme", "transfer", &dst, &src, "ruSt"]).unwrap();
+        assert!(run(args).unwrap_err().to_string().contains("has no ruSt chunk"));
+
+        // the global input options apply to the destination too
+        let mut bad_crc = testing_png().as_bytes();
+        let idat_crc = bad_crc.len() - 12 - 1;
+        bad_crc[idat_crc] ^= 1;
+        std::fs::write(&dst, &bad_crc).unwrap();
+        assert!(run(Cli::try_parse_from(["pngme", "transfer", &src, &dst, "ruSt", "--output", &output]).unwrap()).is_err());
+        run(Cli::try_parse_from(["pngme", "--no-crc-check", "transfer", &src, &dst, "ruSt", "--output", &output]).unwrap()).unwrap();
+
+        for path in [src, dst, output] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_save_png_progress_writes_same_bytes() {
+        let png = testing_png();
+        let plain = temp_path("save_plain.png");
+        let with_progress = temp_path("save_progress.png");
+
+        save_png(&png, plain.clone(), &OutputOptions::default()).unwrap();
+        save_png(&png, with_progress.clone(), &OutputOptions { progress: true, ..Default::default() }).unwrap();
+
+        assert_eq!(std::fs::read(&plain).unwrap(), std::fs::read(&with_progress).unwrap());
+        std::fs::remove_file(plain).unwrap();
+        std::fs::remove_file(with_progress).unwrap();
+    }
+
+    #[test]
+    fn test_remove_last() {
+        let path = temp_path("remove_last.png");
+        let png = png_from_chunks(&[("IHDR", &[0; 13]), ("IDAT", &[1]), ("ruSt", b"first"), ("ruSt", b"second"), ("IEND", &[])]);
+        std::fs::write(&path, png.as_bytes()).unwrap();
+
+        let args = Cli::try_parse_from(["pngme", "remove", &path, "ruSt", "--last", "--yes"]).unwrap();
+        run(args).unwrap();
+
+        let saved = Png::try_from(std::fs::read(&path).unwrap().as_slice()).unwrap();
+        let remaining: Vec<&[u8]> = saved.chunks_by_type("ruSt").iter().map(|chunk| chunk.data()).collect();
+        assert_eq!(remaining, [b"first"]);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_remove_missing_chunk_is_an_error() {
+        let path = temp_path("remove_missing.png");
+        std::fs::write(&path, testing_png().as_bytes()).unwrap();
+
+        for extra in [None, Some("--last")] {
+            let args = ["pngme", "remove", &path, "zzZz", "--yes"].into_iter().chain(extra);
+            let err = run(Cli::try_parse_from(args).unwrap()).err().unwrap();
+            assert!(matches!(err.downcast_ref::<PngError>(), Some(PngError::ChunkNotFound(_))));
+        }
+        assert_eq!(std::fs::read(&path).unwrap(), testing_png().as_bytes());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_remove_all() {
+        let path = temp_path("remove_all.png");
+        let png = png_from_chunks(&[("IHDR", &[0; 13]), ("ruSt", b"1"), ("IDAT", &[1]), ("ruSt", b"2"), ("ruSt", b"3"), ("IEND", &[])]);
+        std::fs::write(&path, png.as_bytes()).unwrap();
+
+        assert!(run(Cli::try_parse_from(["pngme", "remove", &path, "I*", "--all", "--yes"]).unwrap()).is_err());
+        run(Cli::try_parse_from(["pngme", "remove", &path, "ruSt", "--all", "--yes"]).unwrap()).unwrap();
+        let saved = Png::try_from(std::fs::read(&path).unwrap().as_slice()).unwrap();
+        let types: Vec<String> = saved.iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "IDAT", "IEND"]);
+
+        assert!(run(Cli::try_parse_from(["pngme", "remove", &path, "ruSt", "--all", "--yes"]).unwrap()).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_embedded_png() {
+        let png = testing_png().as_bytes();
+        let mut bytes = b"polyglot header".to_vec();
+        bytes.extend_from_slice(&png);
+
+        let scan = InputOptions { scan: true, ..Default::default() };
+        assert_eq!(embedded_png(&bytes, &scan), Some(png.as_slice()));
+        let offset = InputOptions { offset: Some(15), ..Default::default() };
+        assert_eq!(embedded_png(&bytes, &offset), Some(png.as_slice()));
+        assert_eq!(embedded_png(&bytes, &InputOptions::default()), Some(bytes.as_slice()));
+
+        bytes.extend_from_slice(&png);
+        assert_eq!(embedded_png(&bytes, &scan), Some(png.as_slice()));
+        assert_eq!(embedded_png(b"junk", &scan), None);
+    }
+
+    #[test]
+    fn test_scan_loads_png_after_junk() {
+        let path = temp_path("scan.png");
+        let mut bytes = vec![0xff; 37];
+        bytes.extend_from_slice(&testing_png().as_bytes());
+        std::fs::write(&path, &bytes).unwrap();
+
+        let err = load_png(path.clone(), &InputOptions::default()).err().unwrap();
+        assert!(err.to_string().contains("does not start with the png signature"));
+        let png = load_png(path.clone(), &InputOptions { scan: true, ..Default::default() }).unwrap();
+        assert_eq!(png.chunks().len(), 3);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_max_chunks_flag() {
+        let path = temp_path("max_chunks.png");
+        std::fs::write(&path, testing_png().as_bytes()).unwrap();
+
+        let args = Cli::try_parse_from(["pngme", "list", &path, "--max-chunks", "2"]).unwrap();
+        assert!(run(args).unwrap_err().to_string().contains("more than 2 chunks"));
+        let args = Cli::try_parse_from(["pngme", "list", &path, "--max-chunks", "3"]).unwrap();
+        assert!(run(args).is_ok());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_no_crc_check() {
+        let path = temp_path("bad_crc.png");
+        let png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13]),
+            Chunk::new_with_crc(ChunkType::from_str("IDAT").unwrap(), vec![1, 2, 3], 0x12345678),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]),
+        ]);
+        std::fs::write(&path, png.as_bytes()).unwrap();
+
+        assert!(load_png(path.clone(), &InputOptions::default()).is_err());
+        let lenient = InputOptions { no_crc_check: true, ..Default::default() };
+        let loaded = load_png(path.clone(), &lenient).unwrap();
+        assert_eq!(loaded.chunks()[1].crc(), 0x12345678);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_expand_output_template() {
+        assert_eq!(expand_output_template("{stem}.encoded.{ext}", "foo.png", "ruSt").unwrap(), "foo.encoded.png");
+        assert_eq!(expand_output_template("{stem}-{type}.png", "images/foo.png", "ruSt").unwrap(), "images/foo-ruSt.png");
+        assert_eq!(expand_output_template("{stem}.{ext}", "noext", "ruSt").unwrap(), "noext.");
+        assert_eq!(
+            expand_output_template("{stem}.{name}", "foo.png", "ruSt").err().unwrap().to_string(),
+            "Unknown placeholder {name} in --output-template; expected {stem}, {ext} or {type}"
+        );
+        assert!(expand_output_template("{stem", "foo.png", "ruSt").err().unwrap().to_string().starts_with("Unclosed {"));
+
+        let path = temp_path("template_input.png");
+        std::fs::write(&path, testing_png().as_bytes()).unwrap();
+        run(Cli::try_parse_from(["pngme", "encode", &path, "ruSt", "hi", "--output-template", "{stem}.{type}.{ext}"]).unwrap()).unwrap();
+        let output = temp_path("template_input.ruSt.png");
+        assert_eq!(load_png(output.clone(), &InputOptions::default()).unwrap().chunk_by_type("ruSt").unwrap().data(), b"hi");
+        assert!(Cli::try_parse_from(["pngme", "encode", &path, "ruSt", "hi", "out.png", "--output-template", "{stem}"]).is_err());
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_estimate_matches_saved_size() {
+        let output = temp_path("estimate.png");
+        for (args, path) in [(&[][..], output.clone()), (&["--encoding", "base64"][..], output.clone()), (&["--split", "3"][..], format!("{}.gz", output))] {
+            let mut png = testing_png();
+            let options = encode_options(args);
+            let input_len = png.encoded_len();
+            encode(&mut png, "ruSt", b"a message to estimate", &options).unwrap();
+
+            let estimate = estimated_size(&png, &path);
+            let written = save_png(&png, path.clone(), &OutputOptions::default()).unwrap();
+            assert_eq!(estimate, written);
+            assert_eq!(estimate, std::fs::metadata(&path).unwrap().len() as usize);
+            if options.split.is_none() {
+                let message_len = png.chunk_by_type("ruSt").unwrap().data_length();
+                assert_eq!(estimate, input_len + 12 + message_len);
+            }
+            std::fs::remove_file(path).unwrap();
+        }
+
+        let input = temp_path("estimate_input.png");
+        std::fs::write(&input, testing_png().as_bytes()).unwrap();
+        run(Cli::try_parse_from(["pngme", "encode", &input, "ruSt", "hi", &output, "--estimate"]).unwrap()).unwrap();
+        assert!(!Path::new(&output).exists());
+        std::fs::remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_encode_hex_message() {
+        let path = temp_path("hex_message.png");
+        std::fs::write(&path, testing_png().as_bytes()).unwrap();
+
+        run(Cli::try_parse_from(["pngme", "encode", &path, "ruSt", "48656c6c6f", &path, "--hex"]).unwrap()).unwrap();
+        let saved = load_png(path.clone(), &InputOptions::default()).unwrap();
+        assert_eq!(saved.chunk_by_type("ruSt").unwrap().data(), b"Hello");
+        assert_eq!(decode_to_string(&saved, "ruSt"), (1, String::from("Hello\n")));
+
+        for invalid in ["486", "zz"] {
+            let err = run(Cli::try_parse_from(["pngme", "encode", &path, "ruSt", invalid, &path, "--hex"]).unwrap()).err().unwrap();
+            assert!(err.to_string().starts_with("Invalid --hex message"), "{}", err);
+        }
+        assert_eq!(hex_message(b"dead  beef\n").err().unwrap().to_string(), "Invalid --hex message: Invalid hex digit ' '");
+        assert_eq!(hex_message(b"deadbeef\n").unwrap(), [0xde, 0xad, 0xbe, 0xef]);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_encode_no_overwrite() {
+        let input = temp_path("no_overwrite_in.png");
+        let output = temp_path("no_overwrite_out.png");
+        std::fs::write(&input, testing_png().as_bytes()).unwrap();
+        let _ = std::fs::remove_file(&output);
+
+        let encode = |flag: &str| run(Cli::try_parse_from(["pngme", "encode", &input, "ruSt", "hi", &output, flag]).unwrap());
+        encode("--no-overwrite").unwrap();
+        let err = encode("--no-overwrite").err().unwrap();
+        assert!(err.to_string().contains("already exists"));
+        encode("--overwrite").unwrap();
+        assert!(Cli::try_parse_from(["pngme", "encode", &input, "ruSt", "hi", "--overwrite", "--no-overwrite"]).is_err());
+
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_recompute_all_crcs() {
+        let path = temp_path("recompute_crcs.png");
+        let bad = Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13]),
+            Chunk::new_with_crc(ChunkType::from_str("IDAT").unwrap(), vec![1, 2, 3], 0x12345678),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]),
+        ]);
+        std::fs::write(&path, bad.as_bytes()).unwrap();
+
+        run(Cli::try_parse_from(["pngme", "--no-crc-check", "encode", &path, "ruSt", "hi", &path, "--recompute-all-crcs"]).unwrap()).unwrap();
+        let saved = load_png(path.clone(), &InputOptions::default()).unwrap();
+        assert!(saved.iter().all(Chunk::checksum_matches));
+
+        std::fs::write(&path, bad.as_bytes()).unwrap();
+        run(Cli::try_parse_from(["pngme", "--no-crc-check", "replace", &path, "IDAT", "new", "--recompute-all-crcs"]).unwrap()).unwrap();
+        let saved = load_png(path.clone(), &InputOptions::default()).unwrap();
+        assert!(saved.iter().all(Chunk::checksum_matches));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    fn get_itxt_to_string(png: &Png, keyword: Option<&str>) -> String {
+        let mut out = Vec::new();
+        get_itxt(png, keyword, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_set_and_get_itxt() {
+        let mut png = testing_png();
+        set_itxt(&mut png, &InternationalText::new("Title", "Würfel 🎲")).unwrap();
+        let compressed = InternationalText { compressed: true, ..InternationalText::new("Comment", &"long text ".repeat(20)) };
+        set_itxt(&mut png, &compressed).unwrap();
+        set_itxt(&mut png, &InternationalText::new("Title", "Dice")).unwrap();
+
+        let types: Vec<String> = png.iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "iTXt", "iTXt", "IDAT", "IEND"]);
+        assert_eq!(get_itxt_to_string(&png, Some("Title")), "Dice\n");
+        assert_eq!(get_itxt_to_string(&png, Some("Comment")), format!("{}\n", "long text ".repeat(20)));
+        assert!(get_itxt_to_string(&png, None).starts_with("Title: Dice\nComment: long text"));
+        assert_eq!(get_itxt_to_string(&png, Some("Author")), "");
+    }
+
+    #[test]
+    fn test_retype_command() {
+        let path = temp_path("retype.png");
+        let png = png_from_chunks(&[("IHDR", &[0; 13]), ("IDAT", &[1]), ("ruSt", b"payload"), ("IEND", &[])]);
+        std::fs::write(&path, png.as_bytes()).unwrap();
+
+        for invalid in ["teX", "text", "ru1t"] {
+            let args = Cli::try_parse_from(["pngme", "retype", &path, "ruSt", invalid]).unwrap();
+            assert!(run(args).is_err());
+        }
+        let args = Cli::try_parse_from(["pngme", "retype", &path, "abCd", "teXt"]).unwrap();
+        assert!(run(args).is_err());
+
+        let args = Cli::try_parse_from(["pngme", "retype", &path, "ruSt", "teXt"]).unwrap();
+        run(args).unwrap();
+        let saved = Png::try_from(std::fs::read(&path).unwrap().as_slice()).unwrap();
+        assert_eq!(saved.chunks()[2].chunk_type().to_string(), "teXt");
+        assert_eq!(saved.chunks()[2].data(), b"payload");
+
+        assert!(run(Cli::try_parse_from(["pngme", "retype", &path, "teXt", "Rust"]).unwrap()).is_err());
+        run(Cli::try_parse_from(["pngme", "retype", &path, "teXt", "Rust", "--fix-reserved"]).unwrap()).unwrap();
+        let saved = Png::try_from(std::fs::read(&path).unwrap().as_slice()).unwrap();
+        assert_eq!(saved.chunks()[2].chunk_type().to_string(), "RuSt");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_extension_warning() {
+        assert!(extension_warning("output.png").is_none());
+        assert!(extension_warning("OUTPUT.PNG").is_none());
+        assert!(extension_warning("-").is_none());
+        assert!(extension_warning("output.pmg").unwrap().contains("output.pmg does not end in .png"));
+        assert!(extension_warning("output").is_some());
+        assert!(extension_warning("output.png.gz").is_none());
+        assert!(extension_warning("output.gz").unwrap().contains("output does not end in .png"));
+    }
+
+    #[test]
+    fn test_encode_force_silences_extension_warning() {
+        let output = OutputOptions::default();
+        assert!(!encode_output_options(&output, &encode_options(&[])).no_extension_check);
+        assert!(encode_output_options(&output, &encode_options(&["--force"])).no_extension_check);
+
+        let output = OutputOptions { no_extension_check: true, verify_output: true, ..OutputOptions::default() };
+        let combined = encode_output_options(&output, &encode_options(&[]));
+        assert!(combined.no_extension_check && combined.verify_output);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_preserve_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (input, output) = (temp_path("mode_input.png"), temp_path("mode_output.png"));
+        std::fs::write(&input, testing_png().as_bytes()).unwrap();
+        std::fs::set_permissions(&input, std::fs::Permissions::from_mode(0o640)).unwrap();
+        let mode = |path: &str| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        let _ = std::fs::remove_file(&output);
+        run(Cli::try_parse_from(["pngme", "encode", &input, "ruSt", "hi", &output, "--preserve-mode"]).unwrap()).unwrap();
+        assert_eq!(mode(&output), 0o640);
+
+        std::fs::remove_file(&output).unwrap();
+        std::fs::set_permissions(&input, std::fs::Permissions::from_mode(0o604)).unwrap();
+        run(Cli::try_parse_from(["pngme", "encode", &input, "ruSt", "hi", &output]).unwrap()).unwrap();
+        assert_ne!(mode(&output), 0o604);
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_canonical_saves_match() {
+        let a = png_from_chunks(&[("IHDR", &[0; 13]), ("tEXt", b"Author\0me"), ("gAMA", &[0; 4]), ("IDAT", &[1]), ("IEND", &[])]);
+        let b = png_from_chunks(&[("IHDR", &[0; 13]), ("gAMA", &[0; 4]), ("tEXt", b"Author\0me"), ("IDAT", &[1]), ("IEND", &[])]);
+        let (path_a, path_b) = (temp_path("canonical_a.png"), temp_path("canonical_b.png"));
+        let options = OutputOptions { canonical: true, ..Default::default() };
+
+        save_png(&a, path_a.clone(), &options).unwrap();
+        save_png(&b, path_b.clone(), &options).unwrap();
+        assert_eq!(std::fs::read(&path_a).unwrap(), std::fs::read(&path_b).unwrap());
+
+        save_png(&a, path_a.clone(), &OutputOptions::default()).unwrap();
+        assert_ne!(std::fs::read(&path_a).unwrap(), std::fs::read(&path_b).unwrap());
+        std::fs::remove_file(path_a).unwrap();
+        std::fs::remove_file(path_b).unwrap();
+    }
+
+    #[test]
+    fn test_gzip_round_trip() {
+        let path = temp_path("round_trip.png.gz");
+        let options = OutputOptions { verify_output: true, ..Default::default() };
+        let written = save_png(&testing_png(), path.clone(), &options).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(bytes.len(), written);
+        assert!(gzip::is_gzip(&bytes));
+        let loaded = load_png(path.clone(), &InputOptions::default()).unwrap();
+        assert_eq!(loaded.as_bytes(), testing_png().as_bytes());
+
+        std::fs::write(&path, gzip::compress(b"not a png")).unwrap();
+        let err = load_png(path.clone(), &InputOptions::default()).err().unwrap();
+        assert!(err.to_string().contains("is gzip-compressed but doesn't contain a png"));
+
+        std::fs::write(&path, &bytes[..bytes.len() - 4]).unwrap();
+        let err = load_png(path.clone(), &InputOptions::default()).err().unwrap();
+        assert!(err.to_string().starts_with("Unable to decompress"));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_verify_output_catches_truncated_write() {
+        let path = temp_path("verify_output.png");
+        let bytes = testing_png().as_bytes();
+
+        save_png(&testing_png(), path.clone(), &OutputOptions { verify_output: true, ..Default::default() }).unwrap();
+
+        std::fs::write(&path, &bytes[..bytes.len() - 5]).unwrap();
+        let err = verify_saved(&path, bytes.len()).unwrap_err();
+        assert!(err.to_string().contains(&format!("wrote {} bytes but found {}", bytes.len(), bytes.len() - 5)));
+        assert!(verify_saved(&path, bytes.len() - 5).unwrap_err().to_string().contains("Verifying"));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_encode_raw_type() {
+        let mut png = testing_png();
+        let err = encode(&mut png, "ruSt", b"raw", &encode_options(&["--raw-type", "0xDEADBEEF"])).unwrap_err();
+        assert!(err.to_string().contains("critical"));
+
+        encode(&mut png, "ruSt", b"raw", &encode_options(&["--raw-type", "0xDEADBEEF", "--force"])).unwrap();
+        let chunk = png.chunks().last().unwrap();
+        assert_eq!(chunk.chunk_type().bytes(), [0xde, 0xad, 0xbe, 0xef]);
+        assert!(!chunk.chunk_type().is_valid());
+        assert_eq!(chunk.data(), b"raw");
+
+        let args = ["pngme", "encode", "file.png", "ruSt", "message", "--raw-type"];
+        assert!(Cli::try_parse_from(args.iter().chain(["0xDEAD"].iter())).is_err());
+        assert!(Cli::try_parse_from(args.iter().chain(["nothex!!"].iter())).is_err());
+    }
+
+    #[test]
+    fn test_utf8_warning() {
+        let binary: &[u8] = b"\x89PNG\xff\xfe";
+        let message = read_message("-", binary).unwrap();
+
+        let warning = utf8_warning(&message, &encode_options(&[])).unwrap();
+        assert!(warning.contains("not valid UTF-8"));
+        assert!(warning.contains("--raw"));
+        assert!(utf8_warning(&message, &encode_options(&["--allow-invalid-utf8"])).is_none());
+        assert!(utf8_warning(&message, &encode_options(&["--encoding", "base64"])).is_none());
+        assert!(utf8_warning("héllo".as_bytes(), &encode_options(&[])).is_none());
+    }
+
+    #[test]
+    fn test_encode_message_from_stdin() {
+        let stdin: &[u8] = b"piped secret\n\xff";
+        let message = read_message("-", stdin).unwrap();
+        assert_eq!(message, b"piped secret\n\xff");
+        assert_eq!(read_message("literal", stdin).unwrap(), b"literal");
+
+        let mut png = testing_png();
+        encode(&mut png, "ruSt", &message, &EncodeOptions::default()).unwrap();
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), message.as_slice());
+    }
+
+    #[test]
+    fn test_extract_writes_each_chunk() {
+        let png = png_from_chunks(&[
+            ("IHDR", &[0; 13]),
+            ("ruSt", b"first"),
+            ("ruSt", b"second"),
+            ("IEND", &[]),
+        ]);
+        let dir = PathBuf::from(temp_path("extract"));
+
+        let written = extract(&png, &dir.join("nested"), &[]).unwrap();
+        let names: Vec<String> = written.iter().map(|file| file.file_name().unwrap().to_string_lossy().into_owned()).collect();
+        assert_eq!(names, ["0000_IHDR.bin", "0001_ruSt.bin", "0002_ruSt.bin", "0003_IEND.bin"]);
+        assert_eq!(std::fs::read(&written[0]).unwrap(), [0; 13]);
+        assert_eq!(std::fs::read(&written[1]).unwrap(), b"first");
+        assert_eq!(std::fs::read(&written[2]).unwrap(), b"second");
+        assert!(std::fs::read(&written[3]).unwrap().is_empty());
+
+        let written = extract(&png, &dir.join("filtered"), &[String::from("ruSt")]).unwrap();
+        assert_eq!(written.len(), 2);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_set_and_get_ztxt() {
+        let mut png = testing_png();
+        let long = "Grüße aus Köln. ".repeat(50);
+        set_ztxt(&mut png, &CompressedText::new("Comment", "draft")).unwrap();
+        set_ztxt(&mut png, &CompressedText::new("Comment", &long)).unwrap();
+
+        let types: Vec<String> = png.iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "zTXt", "IDAT", "IEND"]);
+        assert!(png.chunk_by_type("zTXt").unwrap().data().len() < long.len());
+
+        let mut out = Vec::new();
+        assert_eq!(get_ztxt(&png, "Comment", &mut out).unwrap(), 1);
+        assert_eq!(String::from_utf8(out).unwrap(), format!("{}\n", long));
+        assert_eq!(get_ztxt(&png, "Title", &mut std::io::sink()).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_preserve_times_on_in_place_encode() {
+        let path = temp_path("preserve_times.png");
+        save_png(&testing_png(), path.clone(), &OutputOptions::default()).unwrap();
+        let old = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000_000);
+        std::fs::File::options().write(true).open(&path).unwrap()
+            .set_times(FileTimes::new().set_accessed(old).set_modified(old)).unwrap();
+
+        let mut png = load_png(path.clone(), &InputOptions::default()).unwrap();
+        encode(&mut png, "ruSt", b"message", &EncodeOptions::default()).unwrap();
+        save_png(&png, path.clone(), &OutputOptions { preserve_times: true, ..Default::default() }).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().modified().unwrap(), old);
+        assert!(load_png(path.clone(), &InputOptions::default()).unwrap().chunk_by_type("ruSt").is_some());
+
+        save_png(&png, path.clone(), &OutputOptions::default()).unwrap();
+        assert_ne!(std::fs::metadata(&path).unwrap().modified().unwrap(), old);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_meta_round_trip() {
+        let mut png = testing_png();
+        assert!(read_meta(&png).unwrap().is_empty());
+        assert_eq!(meta_get(&png, "author").unwrap(), None);
+
+        meta_set(&mut png, "author", "Jane \"JD\" Doe").unwrap();
+        meta_set(&mut png, "license", "CC-BY").unwrap();
+        meta_set(&mut png, "author", "Jane Doe").unwrap();
+
+        assert_eq!(png.chunks_by_type("meTa").len(), 1);
+        let types: Vec<String> = png.iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "meTa", "IDAT", "IEND"]);
+        assert_eq!(meta_get(&png, "author").unwrap().as_deref(), Some("Jane Doe"));
+        assert_eq!(meta_get(&png, "license").unwrap().as_deref(), Some("CC-BY"));
+        let keys: Vec<String> = read_meta(&png).unwrap().into_iter().map(|(key, _)| key).collect();
+        assert_eq!(keys, ["author", "license"]);
+
+        let reloaded = Png::try_from(png.as_bytes()).unwrap();
+        assert_eq!(meta_get(&reloaded, "license").unwrap().as_deref(), Some("CC-BY"));
+    }
+
+    #[test]
+    fn test_get_itxt_missing_keyword_is_an_error() {
+        let path = temp_path("get_itxt_missing.png");
+        std::fs::write(&path, testing_png().as_bytes()).unwrap();
+        for args in [vec!["pngme", "get-itxt", &path, "Title"], vec!["pngme", "get-itxt", &path]] {
+            let err = run(Cli::try_parse_from(args).unwrap()).err().unwrap();
+            assert_eq!(err.to_string(), "Nothing to decode");
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_get_ztxt_missing_keyword_is_an_error() {
+        let path = temp_path("get_ztxt_missing.png");
+        std::fs::write(&path, testing_png().as_bytes()).unwrap();
+        let err = run(Cli::try_parse_from(["pngme", "get-ztxt", &path, "Comment"]).unwrap()).err().unwrap();
+        assert_eq!(err.to_string(), "Nothing to decode");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_meta_rejects_non_object() {
+        let mut png = png_from_chunks(&[("IHDR", &[0; 13]), ("meTa", b"[1, 2]"), ("IEND", &[])]);
+        assert!(meta_set(&mut png, "key", "value").is_err());
+    }
 }
@@ -1,125 +1,657 @@
-use std::{fmt::Display, io::{BufReader, Read}};
-
-use crate::{chunk::Chunk, chunk_type::ChunkType};
+use std::{fmt::Display, io::{self, Read, Write}};
+
+use crate::{chunk::{Chunk, ChunkError}, chunk_type::ChunkType, debug, trace};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum PngError {
+    #[error("File is empty")]
+    EmptyInput,
+    #[error("File is only {0} bytes, too short to hold the png signature")]
+    TooShort(usize),
+    #[error("File does not start with the png signature")]
+    InvalidHeader,
+    #[error("Png has no IEND chunk")]
+    MissingIend,
+    #[error("Invalid chunk: {0}")]
+    ChunkError(#[from] ChunkError),
+    #[error("No chunk of type {0}")]
+    ChunkNotFound(String),
+    #[error("Chunk {0} appears after IEND")]
+    ChunkAfterIend(usize),
+    #[error("Png has no IHDR chunk")]
+    MissingIhdr,
+    #[error("IHDR must be the first chunk")]
+    IhdrNotFirst,
+    #[error("Png is an Apple CgBI png: its image data is byte-swapped and premultiplied, so standard decoders can't read it")]
+    AppleCgBi,
+    #[error("IEND must be the last chunk")]
+    IendNotLast,
+    #[error("Png has no IDAT chunk")]
+    MissingIdat,
+    #[error("Png has more than one {0} chunk")]
+    DuplicateChunk(String),
+    #[error("No chunk at index {0}")]
+    IndexOutOfBounds(usize),
+    #[error("Png has more than {0} chunks")]
+    TooManyChunks(usize),
+    #[error("Critical chunk {chunk_type} may appear only once but appears at indices {indices:?}")]
+    DuplicateCriticalChunk { chunk_type: String, indices: Vec<usize> },
+    #[error("Error reading png: {0}")]
+    Io(#[from] io::Error),
+}
 
 pub struct Png{
     chunks: Vec<Chunk>
 }
 
+/// What [`Png::recover`] salvaged from a damaged file.
+pub struct Recovery {
+    pub png: Png,
+    /// The byte offset of each chunk that couldn't be read, with the reason.
+    pub problems: Vec<(usize, ChunkError)>,
+}
+
+/// Parses chunks one at a time as they are read, from [`Png::stream_chunks`]. Iteration stops
+/// at the end of the input or after the first error.
+pub struct ChunkReader<R: Read> {
+    reader: R,
+    offset: usize,
+    done: bool,
+}
+
+impl<R: Read> ChunkReader<R> {
+    /// Reads up to `len` bytes, fewer only if the input ends first. Taking from the reader
+    /// rather than allocating `len` up front keeps a bogus length from reserving gigabytes.
+    fn read_field(&mut self, len: usize, buf: &mut Vec<u8>) -> io::Result<usize> {
+        (&mut self.reader).take(len as u64).read_to_end(buf)
+    }
+
+    fn read_chunk(&mut self) -> Result<Option<Chunk>, PngError> {
+        let start = self.offset;
+        let mut buf = Vec::with_capacity(12);
+        let read = self.read_field(4, &mut buf)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        if read < 4 {
+            return Err(ChunkError::LengthByteRead { offset: 0 }.at(start).into());
+        }
+        let length = u32::from_be_bytes(buf[..4].try_into().unwrap()) as usize;
+        let wanted = 4 + length + 4;
+        let read = self.read_field(wanted, &mut buf)?;
+        self.offset += 4 + read;
+        // a short read fails in Chunk::try_from with the offset of the missing field
+        Chunk::try_from(buf).map(Some).map_err(|err| err.at(start).into())
+    }
+}
+
+impl<R: Read> Iterator for ChunkReader<R> {
+    type Item = Result<Chunk, PngError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let res = self.read_chunk().transpose();
+        self.done = !matches!(res, Some(Ok(_)));
+        res
+    }
+}
+
+/// How forgiving [`Png::from_bytes_with_options`] is with the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Fail with [`PngError::TooManyChunks`] once more than this many chunks have been read.
+    pub max_chunks: usize,
+    /// Reject chunks whose stored crc doesn't match their contents.
+    pub check_crc: bool,
+    /// Read chunks whose length field is wrong with [`Chunk::try_from_repairing_length`], so
+    /// [`Png::validate_lengths`] can report them instead of parsing failing.
+    pub repair_lengths: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions { max_chunks: Png::DEFAULT_MAX_CHUNKS, check_crc: true, repair_lengths: false }
+    }
+}
+
+/// What [`Png::merge_ancillary_from`] does with a chunk whose type the png already has.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnCollision {
+    /// Leave the existing chunks alone and skip the incoming one.
+    #[default]
+    Keep,
+    /// Drop the existing chunks of that type in favour of the incoming ones.
+    Replace,
+}
+
 impl Png {
 
     pub const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
 
-    fn from_chunks(chunks:Vec<Chunk>) -> Png {
+    /// The most times each critical chunk may appear; `IDAT` may repeat freely.
+    pub const MAX_CHUNK_COUNTS: [(&'static str, usize); 3] = [("IHDR", 1), ("PLTE", 1), ("IEND", 1)];
+
+    /// How many chunks `try_from` parses before giving up on a file as malicious.
+    pub const DEFAULT_MAX_CHUNKS: usize = 100_000;
+
+    /// The offset of the first png signature in `bytes`, for pngs embedded inside other files.
+    pub fn find_signature(bytes: &[u8]) -> Option<usize> {
+        Self::find_signatures(bytes).first().copied()
+    }
+
+    /// The offset of every png signature in `bytes`.
+    pub fn find_signatures(bytes: &[u8]) -> Vec<usize> {
+        bytes.windows(Self::STANDARD_HEADER.len())
+            .enumerate()
+            .filter(|(_, window)| *window == Self::STANDARD_HEADER)
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    pub fn from_chunks(chunks:Vec<Chunk>) -> Png {
         Png {
             chunks
         }
     }
 
     pub fn append_chunk(&mut self, chunk:Chunk){
+        debug!("appending chunk {} at index {}", chunk.chunk_type(), self.chunks.len());
         self.chunks.push(chunk)
     }
 
-    pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk,()>{
-        if let Some(idx) = self.chunks.iter().position(|chunk| chunk.chunk_type().to_string() == chunk_type) {
+    /// The index of the first chunk whose type is exactly `chunk_type`.
+    pub fn position_of(&self, chunk_type: &str) -> Option<usize> {
+        self.chunks.iter().position(|chunk| chunk.type_str() == chunk_type)
+    }
+
+    /// The index of the first `IDAT`, where the image data begins. Metadata that decoders must see
+    /// before the image, like text and colour chunks, belongs before it.
+    pub fn first_idat_index(&self) -> Option<usize> {
+        self.position_of("IDAT")
+    }
+
+    /// Inserts `chunk` just before the first `IDAT`, or before `IEND` if there is no `IDAT`.
+    pub fn insert_before_idat(&mut self, chunk: Chunk) -> Result<(), PngError> {
+        let idx = self.first_idat_index()
+            .or_else(|| self.position_of("IEND"))
+            .ok_or(PngError::MissingIend)?;
+        debug!("inserting chunk {} at index {}", chunk.chunk_type(), idx);
+        self.chunks.insert(idx, chunk);
+        Ok(())
+    }
+
+    /// Moves the colour, metadata and text chunks (see [`ChunkType::BEFORE_IDAT_TYPES`]) that
+    /// follow the first `IDAT` to just before it, keeping the moved chunks in their original order.
+    /// Everything else stays put, including APNG frame chunks and anything after `IEND`. Returns
+    /// how many chunks moved.
+    pub fn move_ancillary_before_idat(&mut self) -> usize {
+        let first_idat = match self.first_idat_index() {
+            Some(idx) => idx,
+            None => return 0
+        };
+        let end = self.position_of("IEND").unwrap_or(self.chunks.len());
+        if end < first_idat {
+            return 0;
+        }
+        let after_iend = self.chunks.split_off(end);
+        let (moved, kept): (Vec<Chunk>, Vec<Chunk>) = self.chunks.split_off(first_idat)
+            .into_iter()
+            .partition(|chunk| ChunkType::BEFORE_IDAT_TYPES.contains(&chunk.type_str()));
+        debug!("moving {} ancillary chunks before IDAT", moved.len());
+        let count = moved.len();
+        self.chunks.extend(moved);
+        self.chunks.extend(kept);
+        self.chunks.extend(after_iend);
+        count
+    }
+
+    /// Inserts `chunk` just before `IEND`, so it stays inside the image a decoder reads.
+    pub fn insert_before_iend(&mut self, chunk: Chunk) -> Result<(), PngError> {
+        let idx = self.position_of("IEND").ok_or(PngError::MissingIend)?;
+        debug!("inserting chunk {} at index {}", chunk.chunk_type(), idx);
+        self.chunks.insert(idx, chunk);
+        Ok(())
+    }
+
+    /// Copies every ancillary chunk of `other` in before `IEND`, returning how many were copied.
+    /// Critical chunks are never copied, so the image data and header stay this png's own.
+    pub fn merge_ancillary_from(&mut self, other: &Png, on_collision: OnCollision) -> Result<usize, PngError> {
+        self.position_of("IEND").ok_or(PngError::MissingIend)?;
+        let incoming: Vec<&Chunk> = other.iter().filter(|chunk| !chunk.chunk_type().is_critical()).collect();
+
+        let incoming: Vec<&Chunk> = match on_collision {
+            OnCollision::Keep => incoming.into_iter()
+                .filter(|chunk| self.chunks.iter().all(|existing| existing.chunk_type() != chunk.chunk_type()))
+                .collect(),
+            OnCollision::Replace => {
+                self.chunks.retain(|existing| incoming.iter().all(|chunk| existing.chunk_type() != chunk.chunk_type()));
+                incoming
+            }
+        };
+        debug!("merging {} ancillary chunks", incoming.len());
+        for chunk in incoming.iter() {
+            self.insert_before_iend((*chunk).clone())?;
+        }
+        Ok(incoming.len())
+    }
+
+    /// Rebuilds the first chunk of `chunk_type` with `data` and a fresh crc, keeping its position.
+    pub fn replace_chunk(&mut self, chunk_type: &str, data: Vec<u8>) -> Result<(), PngError> {
+        let idx = self.chunks.iter().position(|chunk| chunk.type_str() == chunk_type)
+            .ok_or_else(|| PngError::ChunkNotFound(chunk_type.to_string()))?;
+        debug!("replacing chunk {} at index {}", chunk_type, idx);
+        self.chunks[idx].set_data(data);
+        Ok(())
+    }
+
+    /// Rebuilds the first chunk of `chunk_type` as `new_type` with a fresh crc, keeping its data and position.
+    pub fn retype_chunk(&mut self, chunk_type: &str, new_type: ChunkType) -> Result<(), PngError> {
+        let idx = self.chunks.iter().position(|chunk| chunk.type_str() == chunk_type)
+            .ok_or_else(|| PngError::ChunkNotFound(chunk_type.to_string()))?;
+        debug!("retyping chunk {} at index {} to {}", chunk_type, idx, new_type);
+        self.chunks[idx] = Chunk::new(new_type, self.chunks[idx].data().to_vec());
+        Ok(())
+    }
+
+    pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk, PngError>{
+        if let Some(idx) = self.chunks.iter().position(|chunk| chunk.type_str() == chunk_type) {
+            debug!("removing chunk {} at index {}", chunk_type, idx);
             Ok(self.chunks.remove(idx))
         }
         else{
-            Err(())
+            Err(PngError::ChunkNotFound(chunk_type.to_string()))
         }
     }
 
-    fn header(&self) -> &[u8; 8] {
+    /// Removes every chunk whose type matches `pattern`, which may be a `*`/`?` glob pattern,
+    /// returning how many were removed.
+    pub fn remove_all_chunks(&mut self, pattern: &str) -> usize {
+        let before = self.chunks.len();
+        self.chunks.retain(|chunk| !chunk.chunk_type().matches(pattern));
+        debug!("removed {} chunks matching {}", before - self.chunks.len(), pattern);
+        before - self.chunks.len()
+    }
+
+    /// Removes every ancillary chunk, leaving only critical ones like `IHDR`, `PLTE`, `IDAT` and
+    /// `IEND`, and returns how many were removed.
+    pub fn strip_ancillary(&mut self) -> usize {
+        self.strip_ancillary_except(&[])
+    }
+
+    /// Like [`Png::strip_ancillary`], but keeps ancillary chunks whose type matches one of `keep`,
+    /// which may be `*`/`?` glob patterns.
+    pub fn strip_ancillary_except(&mut self, keep: &[&str]) -> usize {
+        let before = self.chunks.len();
+        self.chunks.retain(|chunk| {
+            chunk.chunk_type().is_critical() || keep.iter().any(|pattern| chunk.chunk_type().matches(pattern))
+        });
+        debug!("stripped {} ancillary chunks", before - self.chunks.len());
+        before - self.chunks.len()
+    }
+
+    /// Inserts `chunk` so that it ends up at `index`; `index` may be one past the last chunk.
+    pub fn insert_chunk_at(&mut self, index: usize, chunk: Chunk) -> Result<(), PngError> {
+        if index > self.chunks.len() {
+            return Err(PngError::IndexOutOfBounds(index));
+        }
+        debug!("inserting chunk {} at index {}", chunk.chunk_type(), index);
+        self.chunks.insert(index, chunk);
+        Ok(())
+    }
+
+    /// Removes the chunk at `index`, counting from `0` in file order.
+    pub fn remove_chunk_at(&mut self, index: usize) -> Result<Chunk, PngError> {
+        if index >= self.chunks.len() {
+            return Err(PngError::IndexOutOfBounds(index));
+        }
+        debug!("removing chunk {} at index {}", self.chunks[index].chunk_type(), index);
+        Ok(self.chunks.remove(index))
+    }
+
+    /// Like [`Png::remove_chunk`], but removes the last chunk of `chunk_type` instead of the first.
+    pub fn remove_last_chunk(&mut self, chunk_type: &str) -> Result<Chunk, PngError> {
+        let idx = self.chunks.iter().rposition(|chunk| chunk.type_str() == chunk_type)
+            .ok_or_else(|| PngError::ChunkNotFound(chunk_type.to_string()))?;
+        debug!("removing chunk {} at index {}", chunk_type, idx);
+        Ok(self.chunks.remove(idx))
+    }
+
+    pub fn header(&self) -> &[u8; 8] {
         &Self::STANDARD_HEADER
     }
 
-    fn chunks(&self) -> &[Chunk] {
+    pub fn chunks(&self) -> &[Chunk] {
         self.chunks.as_slice()
     }
 
+    /// Iterates over the chunks in file order.
+    ///
+    /// ```
+    /// use pngme::{chunk::Chunk, chunk_type::ChunkType, png::Png};
+    /// use std::str::FromStr;
+    ///
+    /// let png = Png::from_chunks(vec![
+    ///     Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"one".to_vec()),
+    ///     Chunk::new(ChunkType::from_str("ruST").unwrap(), b"two".to_vec()),
+    /// ]);
+    ///
+    /// assert_eq!(png.iter().filter(|chunk| chunk.chunk_type().is_safe_to_copy()).count(), 1);
+    ///
+    /// let mut count = 0;
+    /// for _chunk in &png {
+    ///     count += 1;
+    /// }
+    /// assert_eq!(count, 2);
+    /// ```
+    pub fn iter(&self) -> std::slice::Iter<'_, Chunk> {
+        self.chunks.iter()
+    }
+
+    /// The chunk at `index`, counting from `0` in file order.
+    pub fn chunk_at(&self, index: usize) -> Result<&Chunk, PngError> {
+        self.chunks.get(index).ok_or(PngError::IndexOutOfBounds(index))
+    }
+
+    /// Finds the first chunk whose type matches `chunk_type`, which may be a `*`/`?` glob pattern.
+    /// Matching is case-sensitive; see [`ChunkType::eq_ignore_case`] for the alternative.
     pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk>{
-        self.chunks.iter().find(|chunk| chunk.chunk_type().to_string() == chunk_type)
+        self.chunks.iter().find(|chunk| chunk.chunk_type().matches(chunk_type))
 
     }
 
-    pub fn as_bytes(&self) -> Vec<u8> {
-        Self::STANDARD_HEADER.iter().clone()
-            .chain(
-                self.chunks.iter().clone().map(|chunk| chunk.as_bytes()).flatten().collect::<Vec<u8>>().as_slice()
-            )
-            .copied().collect()
+    /// Like [`Png::chunk_by_type`], but allows editing the chunk in place, e.g. with
+    /// [`Chunk::set_data`].
+    pub fn chunk_by_type_mut(&mut self, chunk_type: &str) -> Option<&mut Chunk> {
+        self.chunks.iter_mut().find(|chunk| chunk.chunk_type().matches(chunk_type))
     }
 
-}
+    /// Finds every chunk whose type matches `chunk_type`, which may be a `*`/`?` glob pattern.
+    pub fn chunks_by_type(&self, chunk_type: &str) -> Vec<&Chunk>{
+        self.chunks.iter().filter(|chunk| chunk.chunk_type().matches(chunk_type)).collect()
+    }
 
-impl TryFrom<&[u8]> for Png {
-    type Error = ();
+    /// The combined size of every chunk's data, excluding length, type and crc fields.
+    pub fn total_data_size(&self) -> usize {
+        self.chunks.iter().map(|chunk| chunk.data_length()).sum()
+    }
 
-    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
-        let mut res = Png {
-            chunks: vec!()
-        };
+    /// Returns `(index, declared, actual)` for every chunk whose length field disagrees with its data.
+    pub fn validate_lengths(&self) -> Vec<(usize, u32, usize)> {
+        self.chunks.iter()
+            .enumerate()
+            .filter(|(_, chunk)| chunk.length() as usize != chunk.data_length())
+            .map(|(idx, chunk)| (idx, chunk.length(), chunk.data_length()))
+            .collect()
+    }
 
-        let mut reader = BufReader::new(value);
+    /// Fails with the index of the first chunk that follows `IEND`, which strict viewers ignore.
+    pub fn validate_iend_position(&self) -> Result<(), PngError> {
+        match self.chunks.iter().position(|chunk| chunk.type_str() == "IEND") {
+            Some(idx) if idx + 1 < self.chunks.len() => Err(PngError::ChunkAfterIend(idx + 1)),
+            _ => Ok(())
+        }
+    }
 
-        // extract header
-        let mut header_buffer: [u8; 8] = [0; 8];
-        reader.read_exact(&mut header_buffer);
-        if(header_buffer != Png::STANDARD_HEADER){
-            return Err(());
+    /// Returns the type and indices of every chunk type in [`Png::MAX_CHUNK_COUNTS`] that appears
+    /// more often than allowed.
+    pub fn duplicate_critical_chunks(&self) -> Vec<(String, Vec<usize>)> {
+        Png::MAX_CHUNK_COUNTS.iter()
+            .filter_map(|(chunk_type, max)| {
+                let indices: Vec<usize> = self.chunks.iter()
+                    .enumerate()
+                    .filter(|(_, chunk)| chunk.type_str() == *chunk_type)
+                    .map(|(idx, _)| idx)
+                    .collect();
+                (indices.len() > *max).then(|| (chunk_type.to_string(), indices))
+            })
+            .collect()
+    }
+
+    /// Fails with the first critical chunk type that appears more often than allowed.
+    pub fn validate_chunk_counts(&self) -> Result<(), PngError> {
+        match self.duplicate_critical_chunks().into_iter().next() {
+            Some((chunk_type, indices)) => Err(PngError::DuplicateCriticalChunk { chunk_type, indices }),
+            None => Ok(())
         }
+    }
 
-        let mut buffer_32: [u8; 4] = [0; 4];
+    /// Whether this is an iOS-optimized png, which starts with a `CgBI` chunk before `IHDR`.
+    /// Its chunks parse normally, but the image data is not standard, so
+    /// [`Png::validate_structure`] rejects it with [`PngError::AppleCgBi`].
+    pub fn is_apple_optimized(&self) -> bool {
+        self.chunks.first().is_some_and(|chunk| chunk.type_str() == "CgBI")
+    }
 
-        let mut total_len = value.len() as u32 - 8; // counter for checking if we've read all bytes
+    /// Checks for exactly one `IHDR` (first), exactly one `IEND` (last) and at least one `IDAT`.
+    pub fn validate_structure(&self) -> Result<(), PngError> {
+        let count = |chunk_type: &str| self.chunks.iter().filter(|chunk| chunk.type_str() == chunk_type).count();
+        let is_at = |slot: Option<&Chunk>, chunk_type: &str| slot.is_some_and(|chunk| chunk.type_str() == chunk_type);
 
-        // loop until end chunk_type
-        loop {
+        match count("IHDR") {
+            0 => return Err(PngError::MissingIhdr),
+            1 => {},
+            _ => return Err(PngError::DuplicateChunk(String::from("IHDR")))
+        }
+        if self.is_apple_optimized() {
+            return Err(PngError::AppleCgBi);
+        }
+        if !is_at(self.chunks.first(), "IHDR") {
+            return Err(PngError::IhdrNotFirst);
+        }
 
-            // exrtact length
-            reader.read_exact(&mut buffer_32);
-            let length = u32::from_be_bytes(buffer_32);
+        match count("IEND") {
+            0 => return Err(PngError::MissingIend),
+            1 => {},
+            _ => return Err(PngError::DuplicateChunk(String::from("IEND")))
+        }
+        if !is_at(self.chunks.last(), "IEND") {
+            return Err(PngError::IendNotLast);
+        }
 
-            //extract chunk type
-            reader.read_exact(&mut buffer_32);
-            match ChunkType::try_from(buffer_32) {
+        if count("IDAT") == 0 {
+            return Err(PngError::MissingIdat);
+        }
+        Ok(())
+    }
 
-                Ok(chunk_type) => {
+    /// Parses `value` like `try_from`, with a configurable chunk limit (so crafted files can't make
+    /// parsing run away) and crc checking.
+    pub fn from_bytes_with_options(value: &[u8], options: &ParseOptions) -> Result<Png, PngError> {
+        let mut res = Png {
+            chunks: vec!()
+        };
 
-                    let chunk_type_str = chunk_type.to_string();
+        Png::check_signature(value)?;
+        let mut offset = Png::STANDARD_HEADER.len();
 
-                    //extract chunk data
-                    let mut chunk_data: Vec<u8> = vec!(0; length as usize);
-                    reader.read_exact(&mut chunk_data);
-                    let chunk = Chunk::new(chunk_type, chunk_data);
+        // loop until we've consumed every byte
+        loop {
+            if res.chunks.len() == options.max_chunks {
+                return Err(PngError::TooManyChunks(options.max_chunks));
+            }
 
-                    //extract crc
-                    reader.read_exact(&mut buffer_32);
-                    let actual_crc = chunk.crc();
+            let chunk = match (options.repair_lengths, options.check_crc) {
+                (true, true) => Chunk::try_from_repairing_length(&value[offset..]),
+                (true, false) => Chunk::try_from_repairing_length(&value[offset..])
+                    .or_else(|_| Chunk::try_from_lenient(&value[offset..])),
+                (false, true) => Chunk::try_from(&value[offset..]),
+                (false, false) => Chunk::try_from_lenient(&value[offset..])
+            };
+            let chunk = chunk.map_err(|err| err.at(offset))?;
+
+            trace!(
+                "parsed chunk {} at index {}: {} bytes, crc {:08x}",
+                chunk.chunk_type(), res.chunks.len(), chunk.length(), chunk.crc()
+            );
+
+            offset += chunk.data_length() + 12;
+            res.chunks.push(chunk);
+
+            if offset == value.len() {
+                break;
+            }
+        }
+        Ok(res)
+    }
 
-                    // validate crc
-                    let crc = u32::from_be_bytes(buffer_32);
-                    if crc != actual_crc {
-                        return Err(());
-                    }
+    /// Checks the signature at the start of `reader`, then returns an iterator that parses each
+    /// chunk as it is read, so callers can act on early chunks without holding the whole file.
+    pub fn stream_chunks<R: Read>(mut reader: R) -> Result<ChunkReader<R>, PngError> {
+        let mut signature = Vec::with_capacity(Png::STANDARD_HEADER.len());
+        (&mut reader).take(Png::STANDARD_HEADER.len() as u64).read_to_end(&mut signature)?;
+        Png::check_signature(&signature)?;
+        Ok(ChunkReader { reader, offset: Png::STANDARD_HEADER.len(), done: false })
+    }
 
-                    // apend to chunk list
-                    res.append_chunk(chunk);
+    /// Parses a whole png from `reader`, like `try_from` does for bytes already in memory.
+    pub fn from_reader(reader: impl Read) -> Result<Png, PngError> {
+        let mut chunks = vec!();
+        for chunk in Png::stream_chunks(reader)? {
+            if chunks.len() == Png::DEFAULT_MAX_CHUNKS {
+                return Err(PngError::TooManyChunks(Png::DEFAULT_MAX_CHUNKS));
+            }
+            chunks.push(chunk?);
+        }
+        if chunks.is_empty() {
+            return Err(ChunkError::LengthByteRead { offset: Png::STANDARD_HEADER.len() }.into());
+        }
+        Ok(Png { chunks })
+    }
 
-                    // decrement our counter
-                    total_len -= length + 12;
+    fn check_signature(value: &[u8]) -> Result<(), PngError> {
+        if value.is_empty() {
+            return Err(PngError::EmptyInput);
+        }
+        if value.len() < Png::STANDARD_HEADER.len() {
+            return Err(PngError::TooShort(value.len()));
+        }
+        if value[..Png::STANDARD_HEADER.len()] != Png::STANDARD_HEADER {
+            return Err(PngError::InvalidHeader);
+        }
+        Ok(())
+    }
 
-                    //check if we're at end
-                    if total_len <= 0 {
-                        break;
-                    }
+    /// Reads every chunk it can from a damaged file instead of giving up at the first problem.
+    /// A chunk with a bad crc is skipped, since its length still says where the next chunk
+    /// starts; any other error ends parsing, as nothing after it can be trusted.
+    pub fn recover(value: &[u8]) -> Result<Recovery, PngError> {
+        Png::check_signature(value)?;
+        let mut res = Recovery { png: Png { chunks: vec!() }, problems: vec!() };
+
+        let mut offset = Png::STANDARD_HEADER.len();
+        while offset < value.len() {
+            match Chunk::try_from(&value[offset..]).map_err(|err| err.at(offset)) {
+                Ok(chunk) => {
+                    offset += chunk.data_length() + 12;
+                    res.png.chunks.push(chunk);
+                },
+                Err(err) if err.is_recoverable() => {
+                    let skipped = Chunk::try_from_lenient(&value[offset..]).map_err(|err| err.at(offset))?;
+                    debug!("skipping chunk {} at byte {} with a bad crc", skipped.chunk_type(), offset);
+                    res.problems.push((offset, err));
+                    offset += skipped.data_length() + 12;
+                },
+                Err(err) => {
+                    debug!("giving up at byte {}: {}", offset, err);
+                    res.problems.push((offset, err));
+                    break;
                 }
+            }
+        }
+        Ok(res)
+    }
 
-                Err(_) => return Err(())
+    /// Puts the chunks in canonical order, so pngs that differ only in how their ancillary chunks
+    /// are arranged serialize identically. Critical chunks stay where they are; each run of
+    /// ancillary chunks between two critical ones is sorted by type bytes, then data, then crc.
+    /// Ancillary chunks never cross a critical chunk, so ordering rules like `tRNS` coming after
+    /// `PLTE` still hold.
+    pub fn canonicalize(&mut self) {
+        for run in self.chunks.split_mut(|chunk| chunk.chunk_type().is_critical()) {
+            run.sort_by(|a, b| {
+                a.chunk_type().cmp(b.chunk_type())
+                    .then_with(|| a.data().cmp(b.data()))
+                    .then_with(|| a.crc().cmp(&b.crc()))
+            });
+        }
+    }
 
+    /// Rebuilds every chunk from its type and data so each crc is correct, e.g. for a file from
+    /// an encoder that gets them wrong. Returns how many crcs changed.
+    pub fn recompute_crcs(&mut self) -> usize {
+        let mut fixed = 0;
+        for chunk in self.chunks.iter_mut() {
+            if !chunk.checksum_matches() {
+                fixed += 1;
             }
+            *chunk = Chunk::new(*chunk.chunk_type(), chunk.data().to_vec());
+        }
+        debug!("recomputed crcs, {} were wrong", fixed);
+        fixed
+    }
+
+    /// Appends an empty `IEND` unless the last chunk already is one, e.g. after [`Png::recover`]
+    /// lost the end of a file.
+    pub fn ensure_iend(&mut self) {
+        if self.chunks.last().is_none_or(|chunk| chunk.type_str() != "IEND") {
+            self.chunks.push(Chunk::new(ChunkType::try_from(*b"IEND").unwrap(), vec!()));
         }
-        Ok(res)
+    }
+
+    /// Streams the signature and every chunk to `w`, producing the same bytes as [`Png::as_bytes`]
+    /// without holding the whole file in memory.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(&Self::STANDARD_HEADER)?;
+        for chunk in self.chunks.iter() {
+            chunk.write_to(w)?;
+        }
+        Ok(())
+    }
+
+    /// The size of the file [`Png::write_to`] produces: the signature plus each chunk's data and
+    /// 12 bytes of length, type and crc.
+    pub fn encoded_len(&self) -> usize {
+        Self::STANDARD_HEADER.len() + self.chunks.iter().map(|chunk| chunk.data_length() + 12).sum::<usize>()
+    }
+
+    /// The whole file. This depends only on the chunks and their order, so equal pngs always
+    /// serialize to identical bytes.
+    pub fn as_bytes(&self) -> Vec<u8> {
+        Self::STANDARD_HEADER.iter().clone()
+            .chain(
+                self.chunks.iter().clone().flat_map(|chunk| chunk.as_bytes()).collect::<Vec<u8>>().as_slice()
+            )
+            .copied().collect()
+    }
+
+}
+
+impl TryFrom<&[u8]> for Png {
+    type Error = PngError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        Png::from_bytes_with_options(value, &ParseOptions::default())
+    }
+}
+
+impl TryFrom<Vec<u8>> for Png {
+    type Error = PngError;
+
+    fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
+        Png::try_from(value.as_slice())
+    }
+}
+
+impl<'a> IntoIterator for &'a Png {
+    type Item = &'a Chunk;
+    type IntoIter = std::slice::Iter<'a, Chunk>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
     }
 }
 
@@ -139,10 +671,8 @@ impl Display for Png {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::chunk_type::ChunkType;
-    use crate::chunk::Chunk;
-    use std::str::FromStr;
     use std::convert::TryFrom;
+    use std::str::FromStr;
 
     fn testing_chunks() -> Vec<Chunk> {
         vec![
@@ -212,7 +742,192 @@ mod tests {
 
         let png = Png::try_from(bytes.as_ref());
 
-        assert!(png.is_err());
+        assert!(matches!(png, Err(PngError::InvalidHeader)));
+    }
+
+    #[test]
+    fn test_recover_truncated_mid_data() {
+        let bytes = testing_png().as_bytes();
+        let last = testing_chunks().last().unwrap().as_bytes();
+        let truncated = &bytes[..bytes.len() - last.len() + 10];
+
+        assert!(Png::try_from(truncated).is_err());
+        let mut recovery = Png::recover(truncated).unwrap();
+        assert_eq!(recovery.png.chunks().len(), 2);
+        assert_eq!(recovery.problems.len(), 1);
+        assert_eq!(recovery.problems[0].0, bytes.len() - last.len());
+        assert!(matches!(recovery.problems[0].1, ChunkError::DataByteRead { .. }));
+
+        recovery.png.ensure_iend();
+        let types: Vec<String> = recovery.png.iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["FrSt", "miDl", "IEND"]);
+        assert!(Png::try_from(recovery.png.as_bytes().as_slice()).is_ok());
+    }
+
+    #[test]
+    fn test_recompute_crcs() {
+        let mut chunks = testing_chunks();
+        chunks[0] = Chunk::new_with_crc(*chunks[0].chunk_type(), chunks[0].data().to_vec(), 0);
+        chunks[2] = Chunk::new_with_crc(*chunks[2].chunk_type(), chunks[2].data().to_vec(), 1);
+        let mut png = Png::from_chunks(chunks);
+
+        assert_eq!(png.recompute_crcs(), 2);
+        assert!(png.iter().all(Chunk::checksum_matches));
+        assert_eq!(png.as_bytes(), testing_png().as_bytes());
+        assert_eq!(png.recompute_crcs(), 0);
+    }
+
+    #[test]
+    fn test_recover_skips_bad_crc() {
+        let mut chunks = testing_chunks();
+        chunks[1] = Chunk::new_with_crc(*chunks[1].chunk_type(), chunks[1].data().to_vec(), 0);
+        let bytes = Png::from_chunks(chunks).as_bytes();
+
+        let recovery = Png::recover(&bytes).unwrap();
+        let types: Vec<String> = recovery.png.iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["FrSt", "LASt"]);
+        assert_eq!(recovery.problems.len(), 1);
+        assert!(matches!(recovery.problems[0], (offset, ChunkError::CrcMismatch) if offset == 8 + testing_chunks()[0].as_bytes().len()));
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let text = |data: &[u8]| Chunk::new(ChunkType::from_str("tEXt").unwrap(), data.to_vec());
+        let chunk = |chunk_type: &str| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), vec![]);
+        let mut a = Png::from_chunks(vec![
+            chunk("IHDR"), text(b"b"), chunk("gAMA"), text(b"a"), chunk("IDAT"), chunk("zzZz"), chunk("aaAa"), chunk("IEND"),
+        ]);
+        let mut b = Png::from_chunks(vec![
+            chunk("IHDR"), text(b"a"), text(b"b"), chunk("gAMA"), chunk("IDAT"), chunk("aaAa"), chunk("zzZz"), chunk("IEND"),
+        ]);
+        assert_ne!(a.as_bytes(), b.as_bytes());
+        a.canonicalize();
+        b.canonicalize();
+        assert_eq!(a.as_bytes(), b.as_bytes());
+        assert_eq!(a.as_bytes(), a.as_bytes());
+
+        let types: Vec<String> = a.iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "gAMA", "tEXt", "tEXt", "IDAT", "aaAa", "zzZz", "IEND"]);
+        assert_eq!(a.chunks()[2].data(), b"a");
+    }
+
+    #[test]
+    fn test_merge_ancillary_from() {
+        let template = Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![9; 13]),
+            Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"template".to_vec()),
+            Chunk::new(ChunkType::from_str("meTa").unwrap(), b"author".to_vec()),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]),
+        ]);
+        let base = || Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13]),
+            Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"base".to_vec()),
+            Chunk::new(ChunkType::from_str("IDAT").unwrap(), vec![1]),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]),
+        ]);
+        let summary = |png: &Png| -> Vec<String> {
+            png.iter().map(|chunk| format!("{}:{}", chunk.chunk_type(), chunk.data().len())).collect()
+        };
+
+        let mut kept = base();
+        assert_eq!(kept.merge_ancillary_from(&template, OnCollision::Keep).unwrap(), 1);
+        assert_eq!(summary(&kept), ["IHDR:13", "ruSt:4", "IDAT:1", "meTa:6", "IEND:0"]);
+
+        let mut replaced = base();
+        assert_eq!(replaced.merge_ancillary_from(&template, OnCollision::Replace).unwrap(), 2);
+        assert_eq!(summary(&replaced), ["IHDR:13", "IDAT:1", "ruSt:8", "meTa:6", "IEND:0"]);
+        assert_eq!(replaced.chunk_by_type("IHDR").unwrap().data(), &[0; 13]);
+
+        let mut no_iend = Png::from_chunks(vec![]);
+        assert!(matches!(no_iend.merge_ancillary_from(&template, OnCollision::Keep), Err(PngError::MissingIend)));
+    }
+
+    #[test]
+    fn test_remove_all_chunks() {
+        let mut png = png_with_types(&["IHDR", "ruSt", "IDAT", "ruSt", "teXt", "IEND"]);
+        assert_eq!(png.remove_all_chunks("ruSt"), 2);
+        assert_eq!(png.remove_all_chunks("ruSt"), 0);
+        assert_eq!(png.remove_all_chunks("?eXt"), 1);
+        let types: Vec<String> = png.iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_strip_ancillary() {
+        let mut png = png_with_types(&["IHDR", "gAMA", "PLTE", "tEXt", "IDAT", "ruSt", "IEND"]);
+        assert_eq!(png.strip_ancillary(), 3);
+        let types: Vec<String> = png.iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "PLTE", "IDAT", "IEND"]);
+        assert_eq!(png.strip_ancillary(), 0);
+
+        let mut png = png_with_types(&["IHDR", "gAMA", "tEXt", "IDAT", "zTXt", "IEND"]);
+        assert_eq!(png.strip_ancillary_except(&["gAMA", "?TXt"]), 1);
+        let types: Vec<String> = png.iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "gAMA", "IDAT", "zTXt", "IEND"]);
+    }
+
+    #[test]
+    fn test_move_ancillary_before_idat() {
+        let mut png = png_with_types(&["IHDR", "gAMA", "IDAT", "tEXt", "IDAT", "pHYs", "ruSt", "IEND"]);
+        assert_eq!(png.move_ancillary_before_idat(), 2);
+        let types: Vec<String> = png.iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "gAMA", "tEXt", "pHYs", "IDAT", "IDAT", "ruSt", "IEND"]);
+
+        assert_eq!(png.move_ancillary_before_idat(), 0);
+        assert_eq!(png_with_types(&["IHDR", "IEND", "tEXt"]).move_ancillary_before_idat(), 0);
+    }
+
+    #[test]
+    fn test_move_ancillary_before_idat_keeps_apng_frames() {
+        let mut png = png_with_types(&["IHDR", "acTL", "fcTL", "IDAT", "fcTL", "fdAT", "tEXt", "fcTL", "fdAT", "IEND", "zTXt"]);
+        assert_eq!(png.move_ancillary_before_idat(), 1);
+        let types: Vec<String> = png.iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "acTL", "fcTL", "tEXt", "IDAT", "fcTL", "fdAT", "fcTL", "fdAT", "IEND", "zTXt"]);
+    }
+
+    #[test]
+    fn test_position_of() {
+        let png = png_with_types(&["IHDR", "tEXt", "IDAT", "IDAT", "IEND"]);
+        assert_eq!(png.position_of("IHDR"), Some(0));
+        assert_eq!(png.position_of("IEND"), Some(4));
+        assert_eq!(png.position_of("ruSt"), None);
+        assert_eq!(png.first_idat_index(), Some(2));
+        assert_eq!(png_with_types(&["IHDR", "IEND"]).first_idat_index(), None);
+    }
+
+    #[test]
+    fn test_duplicate_critical_chunks() {
+        let png = png_with_types(&["IHDR", "IHDR", "PLTE", "IDAT", "IDAT", "IEND"]);
+        assert_eq!(png.duplicate_critical_chunks(), vec![(String::from("IHDR"), vec![0, 1])]);
+        assert!(matches!(
+            png.validate_chunk_counts(),
+            Err(PngError::DuplicateCriticalChunk { chunk_type, indices }) if chunk_type == "IHDR" && indices == [0, 1]
+        ));
+        assert!(png_with_types(&["IHDR", "PLTE", "IDAT", "IDAT", "IEND"]).validate_chunk_counts().is_ok());
+    }
+
+    #[test]
+    fn test_write_to_matches_as_bytes() {
+        let png = testing_png();
+        let mut written = Vec::new();
+        png.write_to(&mut written).unwrap();
+        assert_eq!(written, png.as_bytes());
+        assert_eq!(png.encoded_len(), written.len());
+    }
+
+    #[test]
+    fn test_png_from_owned_vec() {
+        let bytes = testing_png().as_bytes();
+        let png = Png::try_from(bytes.clone()).unwrap();
+        assert_eq!(png.as_bytes(), bytes);
+        assert!(matches!(Png::try_from(Vec::new()), Err(PngError::EmptyInput)));
+    }
+
+    #[test]
+    fn test_empty_and_short_input() {
+        assert!(matches!(Png::try_from(&[][..]), Err(PngError::EmptyInput)));
+        assert!(matches!(Png::try_from(&Png::STANDARD_HEADER[..3]), Err(PngError::TooShort(3))));
+        assert!(matches!(Png::try_from(&Png::STANDARD_HEADER[..]), Err(PngError::ChunkError(_))));
     }
 
     #[test]
@@ -235,6 +950,112 @@ mod tests {
         let png = Png::try_from(chunk_bytes.as_ref());
 
         assert!(png.is_err());
+
+        let bytes: Vec<u8> = Png::STANDARD_HEADER.iter().chain(chunk_bytes.iter()).copied().collect();
+        let png = Png::try_from(bytes.as_ref());
+        assert!(matches!(png, Err(PngError::ChunkError(_))));
+    }
+
+    #[test]
+    fn test_truncated_png() {
+        let png = Png::try_from(&PNG_FILE[..PNG_FILE.len() - 2]);
+        assert!(matches!(png, Err(PngError::ChunkError(ChunkError::CrcByteRead { .. }))));
+    }
+
+    #[test]
+    fn test_detects_apple_cgbi() {
+        let mut bytes = Png::STANDARD_HEADER.to_vec();
+        for (chunk_type, data) in [("CgBI", &[0x50, 0x00, 0x20, 0x06][..]), ("IHDR", &[0; 13]), ("IDAT", &[1]), ("IEND", &[])] {
+            bytes.extend(Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec()).as_bytes());
+        }
+        let png = Png::try_from(bytes.as_slice()).unwrap();
+        assert!(png.is_apple_optimized());
+        assert!(!testing_png().is_apple_optimized());
+        let err = png.validate_structure().err().unwrap();
+        assert!(matches!(err, PngError::AppleCgBi));
+        assert!(err.to_string().contains("Apple CgBI"));
+    }
+
+    #[test]
+    fn test_from_reader() {
+        let bytes = testing_png().as_bytes();
+        assert_eq!(Png::from_reader(bytes.as_slice()).unwrap().as_bytes(), bytes);
+        assert_eq!(Png::from_reader(PNG_FILE.as_slice()).unwrap().as_bytes(), PNG_FILE);
+
+        assert!(matches!(Png::from_reader(&b""[..]), Err(PngError::EmptyInput)));
+        assert!(matches!(Png::from_reader(&bytes[..8]), Err(PngError::ChunkError(ChunkError::LengthByteRead { offset: 8 }))));
+        let start = bytes.len() - testing_chunks().last().unwrap().as_bytes().len();
+        match Png::from_reader(&bytes[..start + 10]) {
+            Err(PngError::ChunkError(ChunkError::DataByteRead { offset, .. })) => assert_eq!(offset, start + 8),
+            _ => panic!("expected a data read error")
+        }
+    }
+
+    #[test]
+    fn test_stream_chunks_stops_after_error() {
+        let mut bytes = testing_png().as_bytes();
+        bytes.extend([0, 0, 0]);
+        let results: Vec<Result<Chunk, PngError>> = Png::stream_chunks(bytes.as_slice()).unwrap().collect();
+        assert_eq!(results.len(), testing_chunks().len() + 1);
+        assert!(matches!(results.last(), Some(Err(PngError::ChunkError(ChunkError::LengthByteRead { .. })))));
+    }
+
+    #[test]
+    fn test_truncated_data_reports_offset() {
+        let bytes = testing_png().as_bytes();
+        let last = testing_chunks().last().unwrap().as_bytes();
+        let start = bytes.len() - last.len();
+
+        let err = Png::try_from(&bytes[..start + 10]).err().unwrap();
+        assert_eq!(err.to_string(), format!("Invalid chunk: Error reading Data bytes of LASt chunk at offset {}", start + 8));
+        match err {
+            PngError::ChunkError(ChunkError::DataByteRead { offset, chunk_type }) => {
+                assert_eq!(offset, start + 8);
+                assert_eq!(chunk_type.to_string(), "LASt");
+            },
+            err => panic!("unexpected error {:?}", err)
+        }
+    }
+
+    #[test]
+    fn test_too_many_chunks() {
+        let empty_chunk = chunk_from_strings("ruSt", "").unwrap().as_bytes();
+        let bytes: Vec<u8> = Png::STANDARD_HEADER.iter().copied()
+            .chain(std::iter::repeat_n(empty_chunk, 1000).flatten())
+            .collect();
+
+        let options = |max_chunks| ParseOptions { max_chunks, ..Default::default() };
+        assert!(matches!(Png::from_bytes_with_options(&bytes, &options(999)), Err(PngError::TooManyChunks(999))));
+        assert_eq!(Png::from_bytes_with_options(&bytes, &options(1000)).unwrap().chunks().len(), 1000);
+        assert_eq!(Png::try_from(bytes.as_slice()).unwrap().chunks().len(), 1000);
+    }
+
+    #[test]
+    fn test_lenient_crc() {
+        let bad_chunk = Chunk::new_with_crc(ChunkType::from_str("ruSt").unwrap(), b"bad".to_vec(), 0);
+        let bytes: Vec<u8> = Png::STANDARD_HEADER.iter().copied()
+            .chain(bad_chunk.as_bytes())
+            .chain(chunk_from_strings("IEND", "").unwrap().as_bytes())
+            .collect();
+
+        assert!(matches!(Png::try_from(bytes.as_slice()), Err(PngError::ChunkError(ChunkError::CrcMismatch))));
+        let lenient = ParseOptions { check_crc: false, ..Default::default() };
+        let png = Png::from_bytes_with_options(&bytes, &lenient).unwrap();
+        assert_eq!(png.chunks()[0].crc(), 0);
+        assert_eq!(png.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_remove_missing_chunk() {
+        let mut png = testing_png();
+        assert!(matches!(png.remove_chunk("TeSt"), Err(PngError::ChunkNotFound(chunk_type)) if chunk_type == "TeSt"));
+        assert!(matches!(png.remove_chunk_at(3), Err(PngError::IndexOutOfBounds(3))));
+        assert!(matches!(png.chunk_at(3), Err(PngError::IndexOutOfBounds(3))));
+        assert_eq!(&png.chunk_at(2).unwrap().chunk_type().to_string(), "LASt");
+        assert_eq!(&png.remove_chunk_at(1).unwrap().chunk_type().to_string(), "miDl");
+        assert!(matches!(png.insert_chunk_at(3, chunk_from_strings("miDl", "").unwrap()), Err(PngError::IndexOutOfBounds(3))));
+        png.insert_chunk_at(2, chunk_from_strings("miDl", "").unwrap()).unwrap();
+        assert_eq!(&png.chunk_at(2).unwrap().chunk_type().to_string(), "miDl");
     }
 
 
@@ -254,6 +1075,40 @@ mod tests {
 
     }
 
+    #[test]
+    fn test_chunk_by_type_mut() {
+        let mut png = testing_png();
+        png.chunk_by_type_mut("miDl").unwrap().set_data(b"edited in place".to_vec());
+        assert!(png.chunk_by_type_mut("noNe").is_none());
+
+        let reread = Png::try_from(png.as_bytes().as_slice()).unwrap();
+        let chunk = reread.chunk_by_type("miDl").unwrap();
+        assert_eq!(chunk.data(), b"edited in place");
+        assert_eq!(chunk.length(), 15);
+        assert_eq!(reread.chunks().len(), testing_chunks().len());
+    }
+
+    #[test]
+    fn test_chunk_by_type_pattern() {
+        let png = testing_png();
+        let chunk = png.chunk_by_type("*St").unwrap();
+        assert_eq!(&chunk.chunk_type().to_string(), "FrSt");
+        let chunk = png.chunk_by_type("mi??").unwrap();
+        assert_eq!(&chunk.chunk_type().to_string(), "miDl");
+        assert!(png.chunk_by_type("Fr").is_none());
+    }
+
+    #[test]
+    fn test_chunks_by_type() {
+        let png = testing_png();
+        let chunks = png.chunks_by_type("??St");
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(&chunks[0].chunk_type().to_string(), "FrSt");
+        assert_eq!(&chunks[1].chunk_type().to_string(), "LASt");
+        assert_eq!(png.chunks_by_type("*").len(), 3);
+        assert_eq!(png.chunks_by_type("miDl").len(), 1);
+    }
+
     #[test]
     fn test_append_chunk() {
         let mut png = testing_png();
@@ -263,6 +1118,183 @@ mod tests {
         assert_eq!(&chunk.data_as_string().unwrap(), "Message");
     }
 
+    #[test]
+    fn test_insert_before_idat() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let idat = png.chunks().iter().position(|chunk| chunk.type_str() == "IDAT").unwrap();
+
+        png.insert_before_idat(chunk_from_strings("sRGB", "\0").unwrap()).unwrap();
+
+        assert_eq!(&png.chunks()[idat].chunk_type().to_string(), "sRGB");
+        assert_eq!(&png.chunks()[idat + 1].chunk_type().to_string(), "IDAT");
+    }
+
+    #[test]
+    fn test_insert_before_idat_without_idat() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("IEND", "").unwrap());
+
+        png.insert_before_idat(chunk_from_strings("gAMA", "gama").unwrap()).unwrap();
+
+        assert_eq!(&png.chunks()[3].chunk_type().to_string(), "gAMA");
+        assert_eq!(&png.chunks()[4].chunk_type().to_string(), "IEND");
+    }
+
+    #[test]
+    fn test_insert_before_idat_missing_iend() {
+        let mut png = testing_png();
+        let res = png.insert_before_idat(chunk_from_strings("gAMA", "gama").unwrap());
+        assert!(matches!(res, Err(PngError::MissingIend)));
+    }
+
+    #[test]
+    fn test_total_data_size() {
+        let png = testing_png();
+        assert_eq!(png.total_data_size(), 20 + 18 + 19);
+    }
+
+    #[test]
+    fn test_validate_lengths() {
+        let repairing = ParseOptions { repair_lengths: true, ..Default::default() };
+        let bytes = testing_png().as_bytes();
+        let midl_length = Png::STANDARD_HEADER.len() + 12 + 20;
+        // one length runs into the next chunk, the other past the end of the file
+        for declared in [25u32, 99] {
+            let mut tampered = bytes.clone();
+            tampered[midl_length..midl_length + 4].copy_from_slice(&declared.to_be_bytes());
+            assert!(Png::try_from(tampered.as_slice()).is_err());
+
+            let png = Png::from_bytes_with_options(&tampered, &repairing).unwrap();
+            assert_eq!(png.chunks().len(), 3);
+            assert_eq!(png.validate_lengths(), vec![(1, declared, 18)]);
+        }
+        assert!(Png::from_bytes_with_options(&bytes, &repairing).unwrap().validate_lengths().is_empty());
+        assert!(testing_png().validate_lengths().is_empty());
+    }
+
+    #[test]
+    fn test_replace_chunk() {
+        let mut png = testing_png();
+        png.replace_chunk("miDl", b"I am the new middle".to_vec()).unwrap();
+
+        let types: Vec<String> = png.chunks().iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["FrSt", "miDl", "LASt"]);
+        assert_eq!(&png.chunks()[0].data_as_string().unwrap(), "I am the first chunk");
+        assert_eq!(&png.chunks()[1].data_as_string().unwrap(), "I am the new middle");
+        assert_eq!(&png.chunks()[2].data_as_string().unwrap(), "I am the last chunk");
+
+        let expected = chunk_from_strings("miDl", "I am the new middle").unwrap();
+        assert_eq!(png.chunks()[1].crc(), expected.crc());
+        assert_eq!(png.chunks()[1].length(), expected.length());
+    }
+
+    #[test]
+    fn test_retype_chunk() {
+        let mut png = testing_png();
+        let old_crc = png.chunks()[1].crc();
+        png.retype_chunk("miDl", ChunkType::from_str("teXt").unwrap()).unwrap();
+
+        let chunk = &png.chunks()[1];
+        assert_eq!(chunk.chunk_type().to_string(), "teXt");
+        assert_eq!(&chunk.data_as_string().unwrap(), "I am another chunk");
+        assert_ne!(chunk.crc(), old_crc);
+        assert!(chunk.checksum_matches());
+        assert!(png.chunk_by_type("miDl").is_none());
+
+        let res = png.retype_chunk("miDl", ChunkType::from_str("teXt").unwrap());
+        assert!(matches!(res, Err(PngError::ChunkNotFound(chunk_type)) if chunk_type == "miDl"));
+    }
+
+    #[test]
+    fn test_replace_missing_chunk() {
+        let mut png = testing_png();
+        let res = png.replace_chunk("TeSt", vec![]);
+        assert!(matches!(res, Err(PngError::ChunkNotFound(chunk_type)) if chunk_type == "TeSt"));
+        assert_eq!(png.chunks().len(), 3);
+    }
+
+    #[test]
+    fn test_validate_iend_position() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert!(png.validate_iend_position().is_ok());
+
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("IEND", "").unwrap());
+        assert!(png.validate_iend_position().is_ok());
+
+        png.append_chunk(chunk_from_strings("ruSt", "hidden").unwrap());
+        assert!(matches!(png.validate_iend_position(), Err(PngError::ChunkAfterIend(4))));
+    }
+
+    #[test]
+    fn test_insert_before_iend() {
+        let mut png = png_with_types(&["IHDR", "IDAT", "IEND"]);
+        png.insert_before_iend(chunk_from_strings("ruSt", "moved").unwrap()).unwrap();
+        let types: Vec<String> = png.chunks().iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "IDAT", "ruSt", "IEND"]);
+
+        let mut png = testing_png();
+        assert!(matches!(png.insert_before_iend(chunk_from_strings("ruSt", "").unwrap()), Err(PngError::MissingIend)));
+    }
+
+    #[test]
+    fn test_remove_last_chunk() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("miDl", "I am the newest").unwrap());
+
+        let removed = png.remove_last_chunk("miDl").unwrap();
+        assert_eq!(&removed.data_as_string().unwrap(), "I am the newest");
+        assert_eq!(&png.chunk_by_type("miDl").unwrap().data_as_string().unwrap(), "I am another chunk");
+        assert!(matches!(png.remove_last_chunk("TeSt"), Err(PngError::ChunkNotFound(_))));
+    }
+
+    #[test]
+    fn test_find_signature() {
+        let mut bytes = b"junk before the image".to_vec();
+        bytes.extend_from_slice(&PNG_FILE);
+        assert_eq!(Png::find_signature(&bytes), Some(21));
+        assert!(Png::try_from(&bytes[21..]).is_ok());
+
+        bytes.extend_from_slice(&PNG_FILE);
+        assert_eq!(Png::find_signatures(&bytes), [21, 21 + PNG_FILE.len()]);
+        assert_eq!(Png::find_signature(b"no png here"), None);
+    }
+
+    fn png_with_types(types: &[&str]) -> Png {
+        Png::from_chunks(types.iter().map(|chunk_type| chunk_from_strings(chunk_type, "").unwrap()).collect())
+    }
+
+    #[test]
+    fn test_validate_structure() {
+        assert!(Png::try_from(&PNG_FILE[..]).unwrap().validate_structure().is_ok());
+        assert!(png_with_types(&["IHDR", "IDAT", "IDAT", "ruSt", "IEND"]).validate_structure().is_ok());
+
+        assert!(matches!(png_with_types(&["IDAT", "IEND"]).validate_structure(), Err(PngError::MissingIhdr)));
+        assert!(matches!(png_with_types(&["ruSt", "IHDR", "IDAT", "IEND"]).validate_structure(), Err(PngError::IhdrNotFirst)));
+        assert!(matches!(png_with_types(&["CgBI", "IHDR", "IDAT", "IEND"]).validate_structure(), Err(PngError::AppleCgBi)));
+        assert!(matches!(png_with_types(&["IHDR", "IDAT"]).validate_structure(), Err(PngError::MissingIend)));
+        assert!(matches!(png_with_types(&["IHDR", "IDAT", "IEND", "ruSt"]).validate_structure(), Err(PngError::IendNotLast)));
+        assert!(matches!(png_with_types(&["IHDR", "ruSt", "IEND"]).validate_structure(), Err(PngError::MissingIdat)));
+        assert!(matches!(
+            png_with_types(&["IHDR", "IHDR", "IDAT", "IEND"]).validate_structure(),
+            Err(PngError::DuplicateChunk(chunk_type)) if chunk_type == "IHDR"
+        ));
+    }
+
+    #[test]
+    fn test_iter() {
+        let png = testing_png();
+        let types: Vec<String> = png.iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["FrSt", "miDl", "LASt"]);
+
+        let mut count = 0;
+        for chunk in &png {
+            assert_eq!(chunk.chunk_type(), png.chunks()[count].chunk_type());
+            count += 1;
+        }
+        assert_eq!(count, png.chunks().len());
+    }
+
     #[test]
     fn test_remove_chunk() {
         let mut png = testing_png();
@@ -0,0 +1,84 @@
+use std::io::{self, Write};
+
+/// Data is handed to the inner writer in blocks of this size so progress updates as it goes.
+pub const BLOCK_SIZE: usize = 64 * 1024;
+const BAR_WIDTH: usize = 30;
+
+/// Wraps a writer and redraws a `[=====     ] written/total bytes` bar on `progress` after every write.
+pub struct ProgressWriter<W: Write, P: Write> {
+    inner: W,
+    progress: P,
+    written: usize,
+    total: usize,
+}
+
+impl<W: Write, P: Write> ProgressWriter<W, P> {
+    pub fn new(inner: W, progress: P, total: usize) -> Self {
+        ProgressWriter { inner, progress, written: 0, total }
+    }
+
+    fn report(&mut self) -> io::Result<()> {
+        let filled = (self.written * BAR_WIDTH).checked_div(self.total).unwrap_or(BAR_WIDTH);
+        write!(
+            self.progress, "\r[{}{}] {}/{} bytes",
+            "=".repeat(filled), " ".repeat(BAR_WIDTH - filled), self.written, self.total
+        )?;
+        self.progress.flush()
+    }
+
+    /// Ends the progress line and flushes the inner writer.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.report()?;
+        writeln!(self.progress)?;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write, P: Write> Write for ProgressWriter<W, P> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.written += written;
+        self.report()?;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Writes all of `bytes` to `inner` in [`BLOCK_SIZE`] blocks, drawing a progress bar on `progress`.
+pub fn write_with_progress<W: Write, P: Write>(inner: W, progress: P, bytes: &[u8]) -> io::Result<()> {
+    let mut writer = ProgressWriter::new(inner, progress, bytes.len());
+    for block in bytes.chunks(BLOCK_SIZE) {
+        writer.write_all(block)?;
+    }
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_with_progress() {
+        let data: Vec<u8> = (0..BLOCK_SIZE * 2 + 10).map(|idx| idx as u8).collect();
+        let mut written = Vec::new();
+        let mut progress = Vec::new();
+        write_with_progress(&mut written, &mut progress, &data).unwrap();
+        assert_eq!(written, data);
+
+        let progress = String::from_utf8(progress).unwrap();
+        assert_eq!(progress.matches('\r').count(), 4);
+        assert!(progress.ends_with(&format!("[{}] {}/{} bytes\n", "=".repeat(BAR_WIDTH), data.len(), data.len())));
+    }
+
+    #[test]
+    fn test_write_with_progress_empty() {
+        let mut written = Vec::new();
+        let mut progress = Vec::new();
+        write_with_progress(&mut written, &mut progress, &[]).unwrap();
+        assert!(written.is_empty());
+        assert!(String::from_utf8(progress).unwrap().contains("0/0 bytes"));
+    }
+}
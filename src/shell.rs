@@ -0,0 +1,131 @@
+use std::io::{BufRead, Write};
+
+use pngme::png::Png;
+
+use crate::{cli::{DecodeOptions, EncodeOptions, ListOptions, OutputOptions}, decode, encode, list, save_png, Result};
+
+const HELP: &str = "commands: list, encode <type> <msg>, decode <type>, remove <type>, save [path], quit";
+
+/// Reads commands line by line from `input` and applies them to `png` until `quit` or end of input.
+pub fn run(mut png: Png, path: String, input: impl BufRead, out: &mut impl Write) -> Result<()> {
+    let mut dirty = false;
+    let mut warned = false;
+    let mut lines = input.lines();
+
+    loop {
+        write!(out, "pngme> ")?;
+        out.flush()?;
+
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => break
+        };
+        let mut words = line.trim().splitn(3, char::is_whitespace);
+        let command = words.next().unwrap_or_default();
+        let args: Vec<&str> = words.map(str::trim).collect();
+
+        if command != "quit" {
+            warned = false;
+        }
+
+        match (command, args.as_slice()) {
+            ("", []) => {},
+            ("list", []) => list(&png, &ListOptions::default(), out)?,
+            ("encode", [chunk_type, message]) => {
+                match encode(&mut png, chunk_type, message.as_bytes(), &EncodeOptions::default()) {
+                    Ok(()) => dirty = true,
+                    Err(err) => writeln!(out, "error: {}", err)?
+                }
+            },
+            ("decode", [chunk_type]) => {
+                match decode(&png, chunk_type, &DecodeOptions::default(), out) {
+                    Ok(0) => writeln!(out, "Nothing to decode")?,
+                    Ok(_) => {},
+                    Err(err) => writeln!(out, "error: {}", err)?
+                }
+            },
+            ("remove", [chunk_type]) => {
+                match png.remove_chunk(chunk_type) {
+                    Ok(_) => dirty = true,
+                    Err(err) => writeln!(out, "error: {}", err)?
+                }
+            },
+            ("save", [] | [_]) => {
+                let target = args.first().map(|target| target.to_string()).unwrap_or_else(|| path.clone());
+                match save_png(&png, target.clone(), &OutputOptions::default()) {
+                    Ok(written) => {
+                        writeln!(out, "wrote {} bytes to {}", written, target)?;
+                        dirty = false;
+                    },
+                    Err(err) => writeln!(out, "error: {}", err)?
+                }
+            },
+            ("quit", []) if dirty && !warned => {
+                writeln!(out, "Unsaved changes; save first or quit again to discard them")?;
+                warned = true;
+            },
+            ("quit", []) => break,
+            _ => writeln!(out, "{}", HELP)?
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(all(test, feature = "fixtures"))]
+mod tests {
+    use super::*;
+    use pngme::fixtures::{self, ColorType};
+
+    fn run_script(png: Png, path: &str, script: &str) -> String {
+        let mut out = Vec::new();
+        run(png, path.to_string(), script.as_bytes(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_shell_encode_decode() {
+        let png = fixtures::minimal_png(1, 1, ColorType::Rgb);
+        let output = run_script(png, "unused.png", "encode ruSt hello there\ndecode ruSt\nremove ruSt\ndecode ruSt\nquit\nquit\n");
+        assert!(output.contains("pngme> hello there\n"));
+        assert!(output.contains("Nothing to decode"));
+    }
+
+    #[test]
+    fn test_shell_quit_warns_about_unsaved_changes() {
+        let png = fixtures::minimal_png(1, 1, ColorType::Rgb);
+        let output = run_script(png, "unused.png", "encode ruSt hello\nquit\nlist\n");
+        assert!(output.contains("Unsaved changes"));
+        // the shell is still running, so the listing after the first quit is printed
+        assert!(output.contains("ruSt"));
+    }
+
+    #[test]
+    fn test_shell_quit_without_changes() {
+        let png = fixtures::minimal_png(1, 1, ColorType::Rgb);
+        let output = run_script(png, "unused.png", "list\nquit\nlist\n");
+        assert!(!output.contains("Unsaved changes"));
+        assert_eq!(output.matches("IHDR").count(), 1);
+    }
+
+    #[test]
+    fn test_shell_save() {
+        let path = std::env::temp_dir().join(format!("pngme-{}-shell_save.png", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let png = fixtures::minimal_png(1, 1, ColorType::Rgb);
+
+        let output = run_script(png, &path, "encode ruSt saved\nsave\nquit\n");
+        assert!(!output.contains("Unsaved changes"));
+
+        let saved = Png::try_from(std::fs::read(&path).unwrap().as_slice()).unwrap();
+        assert_eq!(saved.chunk_by_type("ruSt").unwrap().data(), b"saved");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_shell_unknown_command() {
+        let png = fixtures::minimal_png(1, 1, ColorType::Rgb);
+        let output = run_script(png, "unused.png", "frobnicate\ndecode\n");
+        assert_eq!(output.matches(HELP).count(), 2);
+    }
+}
@@ -0,0 +1,124 @@
+use thiserror::Error;
+
+/// Each piece starts with its sequence index and the total piece count, both big-endian u32s.
+pub const HEADER_LEN: usize = 8;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SplitError {
+    #[error("No pieces to join")]
+    Empty,
+    #[error("Piece is too short to hold a sequence header")]
+    MissingHeader,
+    #[error("Pieces disagree on the total count")]
+    CountMismatch,
+    #[error("Missing piece {0}")]
+    MissingPiece(u32),
+    #[error("Duplicate piece {0}")]
+    DuplicatePiece(u32),
+}
+
+/// Chops `data` into pieces of at most `size` bytes, each prefixed with a sequence header.
+pub fn split_message(data: &[u8], size: usize) -> Vec<Vec<u8>> {
+    let pieces: Vec<&[u8]> = if data.is_empty() { vec!(data) } else { data.chunks(size).collect() };
+    let total = pieces.len() as u32;
+
+    pieces.iter()
+        .enumerate()
+        .map(|(idx, piece)| {
+            (idx as u32).to_be_bytes().iter()
+                .chain(total.to_be_bytes().iter())
+                .chain(piece.iter())
+                .copied()
+                .collect()
+        })
+        .collect()
+}
+
+/// Reassembles pieces produced by [`split_message`], in sequence order regardless of input order.
+pub fn join_pieces(pieces: &[&[u8]]) -> Result<Vec<u8>, SplitError> {
+    let mut parsed: Vec<(u32, u32, &[u8])> = vec!();
+    for piece in pieces {
+        if piece.len() < HEADER_LEN {
+            return Err(SplitError::MissingHeader);
+        }
+        let idx = u32::from_be_bytes(piece[0..4].try_into().unwrap());
+        let total = u32::from_be_bytes(piece[4..8].try_into().unwrap());
+        parsed.push((idx, total, &piece[HEADER_LEN..]));
+    }
+
+    let total = match parsed.first() {
+        Some((_, total, _)) => *total,
+        None => return Err(SplitError::Empty)
+    };
+    if parsed.iter().any(|(_, piece_total, _)| *piece_total != total) {
+        return Err(SplitError::CountMismatch);
+    }
+
+    parsed.sort_by_key(|(idx, _, _)| *idx);
+    for (expected, (idx, _, _)) in parsed.iter().enumerate() {
+        let expected = expected as u32;
+        if *idx < expected {
+            return Err(SplitError::DuplicatePiece(*idx));
+        }
+        if *idx > expected {
+            return Err(SplitError::MissingPiece(expected));
+        }
+    }
+    if parsed.len() as u32 != total {
+        return Err(SplitError::MissingPiece(parsed.len() as u32));
+    }
+
+    Ok(parsed.into_iter().flat_map(|(_, _, data)| data.iter().copied()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn as_slices(pieces: &[Vec<u8>]) -> Vec<&[u8]> {
+        pieces.iter().map(|piece| piece.as_slice()).collect()
+    }
+
+    #[test]
+    fn test_split_message() {
+        let pieces = split_message(b"abcdefghij", 3);
+        assert_eq!(pieces.len(), 4);
+        assert_eq!(pieces[0], [0, 0, 0, 0, 0, 0, 0, 4, b'a', b'b', b'c']);
+        assert_eq!(pieces[3], [0, 0, 0, 3, 0, 0, 0, 4, b'j']);
+    }
+
+    #[test]
+    fn test_split_round_trip() {
+        let message = b"This message is split into four pieces";
+        let pieces = split_message(message, 10);
+        assert_eq!(pieces.len(), 4);
+        assert_eq!(join_pieces(&as_slices(&pieces)).unwrap(), message);
+    }
+
+    #[test]
+    fn test_join_out_of_order() {
+        let mut pieces = split_message(b"abcdefghij", 3);
+        pieces.swap(0, 2);
+        assert_eq!(join_pieces(&as_slices(&pieces)).unwrap(), b"abcdefghij");
+    }
+
+    #[test]
+    fn test_join_missing_piece() {
+        let mut pieces = split_message(b"abcdefghij", 3);
+        pieces.remove(1);
+        assert_eq!(join_pieces(&as_slices(&pieces)), Err(SplitError::MissingPiece(1)));
+        pieces.pop();
+        assert_eq!(join_pieces(&as_slices(&pieces)), Err(SplitError::MissingPiece(1)));
+    }
+
+    #[test]
+    fn test_join_invalid_pieces() {
+        assert_eq!(join_pieces(&[]), Err(SplitError::Empty));
+        assert_eq!(join_pieces(&[b"abc"]), Err(SplitError::MissingHeader));
+
+        let pieces = split_message(b"abcdef", 3);
+        let duplicated = [pieces[0].as_slice(), pieces[0].as_slice()];
+        assert_eq!(join_pieces(&duplicated), Err(SplitError::DuplicatePiece(0)));
+    }
+}
@@ -0,0 +1,19 @@
+use std::{sync::atomic::{AtomicBool, Ordering}, time::Instant};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turns `--timing` reports on or off.
+pub fn init(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Runs `f` and, with `--timing`, reports how long it took on stderr as `[timing] phase: 1.234ms`.
+pub fn timed<T>(phase: &str, f: impl FnOnce() -> T) -> T {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return f();
+    }
+    let start = Instant::now();
+    let res = f();
+    eprintln!("[timing] {}: {:.3}ms", phase, start.elapsed().as_secs_f64() * 1000.0);
+    res
+}
@@ -0,0 +1,181 @@
+use std::fmt::Display;
+
+use crate::{chunk::Chunk, png::{Png, PngError}};
+
+/// The data length above which an ancillary chunk is reported by [`oversized_ancillary_chunks`]
+/// unless told otherwise; few legitimate ones come close.
+pub const DEFAULT_WARN_SIZE: u32 = 64 * 1024;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Issue {
+    NonEmptyIend { index: usize, length: u32 },
+    LengthMismatch { index: usize, declared: u32, actual: usize },
+    ChunkAfterIend { index: usize, chunk_type: String },
+    CrcMismatch { index: usize, stored: u32, computed: u32 },
+    DuplicateCriticalChunk { chunk_type: String, indices: Vec<usize> },
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Issue::NonEmptyIend { index, length } =>
+                write!(f, "chunk {}: IEND must be empty but holds {} bytes", index, length),
+            Issue::LengthMismatch { index, declared, actual } =>
+                write!(f, "chunk {}: length field says {} bytes but holds {}", index, declared, actual),
+            Issue::ChunkAfterIend { index, chunk_type } =>
+                write!(f, "chunk {}: {} appears after IEND", index, chunk_type),
+            Issue::CrcMismatch { index, stored, computed } =>
+                write!(f, "chunk {}: stored crc {:08x} but computed {:08x}", index, stored, computed),
+            Issue::DuplicateCriticalChunk { chunk_type, indices } =>
+                write!(f, "chunks {:?}: {} may appear only once", indices, chunk_type),
+        }
+    }
+}
+
+/// Runs every structural check against `png`, returning the problems found. `strict` adds
+/// checks for oddities that most decoders tolerate, such as chunks after `IEND`.
+pub fn verify(png: &Png, strict: bool) -> Vec<Issue> {
+    let mut issues = vec!();
+    check_iend_empty(png, &mut issues);
+    check_lengths(png, &mut issues);
+    check_crcs(png, &mut issues);
+    if strict {
+        check_chunks_after_iend(png, &mut issues);
+        check_duplicate_critical_chunks(png, &mut issues);
+    }
+    issues
+}
+
+/// The ancillary chunks holding more than `limit` bytes of data, with their indices. Large custom
+/// chunks are a common place to hide data.
+pub fn oversized_ancillary_chunks(png: &Png, limit: u32) -> Vec<(usize, &Chunk)> {
+    png.chunks().iter()
+        .enumerate()
+        .filter(|(_, chunk)| !chunk.chunk_type().is_critical() && chunk.length() > limit)
+        .collect()
+}
+
+fn check_iend_empty(png: &Png, issues: &mut Vec<Issue>) {
+    for (index, chunk) in png.chunks().iter().enumerate() {
+        if chunk.type_str() == "IEND" && chunk.length() != 0 {
+            issues.push(Issue::NonEmptyIend { index, length: chunk.length() });
+        }
+    }
+}
+
+fn check_lengths(png: &Png, issues: &mut Vec<Issue>) {
+    for (index, declared, actual) in png.validate_lengths() {
+        issues.push(Issue::LengthMismatch { index, declared, actual });
+    }
+}
+
+/// Only finds anything in pngs loaded without crc checking.
+fn check_crcs(png: &Png, issues: &mut Vec<Issue>) {
+    for (index, chunk) in png.chunks().iter().enumerate() {
+        let computed = chunk.compute_crc();
+        if computed != chunk.crc() {
+            issues.push(Issue::CrcMismatch { index, stored: chunk.crc(), computed });
+        }
+    }
+}
+
+fn check_chunks_after_iend(png: &Png, issues: &mut Vec<Issue>) {
+    if let Err(PngError::ChunkAfterIend(first)) = png.validate_iend_position() {
+        for (index, chunk) in png.chunks().iter().enumerate().skip(first) {
+            issues.push(Issue::ChunkAfterIend { index, chunk_type: chunk.chunk_type().to_string() });
+        }
+    }
+}
+
+fn check_duplicate_critical_chunks(png: &Png, issues: &mut Vec<Issue>) {
+    for (chunk_type, indices) in png.duplicate_critical_chunks() {
+        issues.push(Issue::DuplicateCriticalChunk { chunk_type, indices });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use crate::png::ParseOptions;
+    use std::str::FromStr;
+
+    fn png_from_chunks(chunks: &[(&str, &[u8])]) -> Png {
+        let bytes: Vec<u8> = Png::STANDARD_HEADER
+            .iter()
+            .copied()
+            .chain(chunks.iter().flat_map(|(chunk_type, data)| {
+                Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec()).as_bytes()
+            }))
+            .collect();
+        Png::try_from(bytes.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn test_verify_valid_png() {
+        let png = png_from_chunks(&[("IHDR", &[0; 13]), ("IDAT", &[1, 2, 3]), ("IEND", &[])]);
+        assert!(verify(&png, false).is_empty());
+    }
+
+    #[test]
+    fn test_verify_non_empty_iend() {
+        let png = png_from_chunks(&[("IHDR", &[0; 13]), ("IDAT", &[1, 2, 3]), ("IEND", b"hidden")]);
+        assert_eq!(verify(&png, false), vec![Issue::NonEmptyIend { index: 2, length: 6 }]);
+    }
+
+    #[test]
+    fn test_verify_length_mismatch() {
+        let mut bytes = png_from_chunks(&[("IHDR", &[0; 13]), ("ruSt", &[0; 4]), ("IEND", &[])]).as_bytes();
+        // the length field of ruSt, after the signature and the 25-byte IHDR chunk
+        bytes[8 + 25 + 3] = 2;
+        let options = ParseOptions { repair_lengths: true, ..Default::default() };
+        let png = Png::from_bytes_with_options(&bytes, &options).unwrap();
+
+        let issues = verify(&png, false);
+        assert_eq!(issues, vec![Issue::LengthMismatch { index: 1, declared: 2, actual: 4 }]);
+        assert_eq!(issues[0].to_string(), "chunk 1: length field says 2 bytes but holds 4");
+    }
+
+    #[test]
+    fn test_verify_crc_mismatch() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let computed = Chunk::new(chunk_type, b"data".to_vec()).crc();
+        let png = Png::from_chunks(vec![Chunk::new_with_crc(chunk_type, b"data".to_vec(), 0xdeadbeef)]);
+
+        assert_eq!(verify(&png, false), vec![Issue::CrcMismatch { index: 0, stored: 0xdeadbeef, computed }]);
+    }
+
+    #[test]
+    fn test_verify_strict_duplicate_ihdr() {
+        let png = png_from_chunks(&[("IHDR", &[0; 13]), ("IHDR", &[0; 13]), ("IDAT", &[1, 2, 3]), ("IEND", &[])]);
+
+        assert!(verify(&png, false).is_empty());
+        let issues = verify(&png, true);
+        assert_eq!(issues, vec![Issue::DuplicateCriticalChunk { chunk_type: String::from("IHDR"), indices: vec![0, 1] }]);
+        assert_eq!(issues[0].to_string(), "chunks [0, 1]: IHDR may appear only once");
+    }
+
+    #[test]
+    fn test_verify_strict_chunk_after_iend() {
+        let png = png_from_chunks(&[("IHDR", &[0; 13]), ("IDAT", &[1, 2, 3]), ("IEND", &[]), ("ruSt", b"hidden")]);
+
+        assert!(verify(&png, false).is_empty());
+        assert_eq!(
+            verify(&png, true),
+            vec![Issue::ChunkAfterIend { index: 3, chunk_type: String::from("ruSt") }]
+        );
+    }
+
+    #[test]
+    fn test_oversized_ancillary_chunks() {
+        let big = vec![0; DEFAULT_WARN_SIZE as usize + 1];
+        let png = png_from_chunks(&[("IHDR", &[0; 13]), ("ruSt", &big), ("IDAT", &big), ("teSt", b"small"), ("IEND", &[])]);
+
+        let oversized: Vec<(usize, String)> = oversized_ancillary_chunks(&png, DEFAULT_WARN_SIZE).iter()
+            .map(|(index, chunk)| (*index, chunk.type_str().to_string()))
+            .collect();
+        assert_eq!(oversized, [(1, String::from("ruSt"))]);
+        assert_eq!(oversized_ancillary_chunks(&png, 4).len(), 2);
+        assert!(oversized_ancillary_chunks(&png, DEFAULT_WARN_SIZE + 1).is_empty());
+    }
+}
@@ -0,0 +1,73 @@
+use std::{ops::ControlFlow, path::Path, thread, time::{Duration, SystemTime}};
+
+/// How often the watched file is checked for changes.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// How long a changed file must stay unchanged before it counts as written, so a burst of
+/// writes triggers one run instead of many.
+pub const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// The size and modification time of `path`, or `None` while it doesn't exist.
+fn stamp(path: &Path) -> Option<(u64, SystemTime)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.len(), metadata.modified().ok()?))
+}
+
+/// Polls `path` and calls `on_change` each time it changes and then settles, until
+/// `on_change` breaks. A file that is deleted and recreated counts as changed.
+pub fn watch(path: &Path, poll: Duration, debounce: Duration, mut on_change: impl FnMut() -> ControlFlow<()>) {
+    let mut last = stamp(path);
+    loop {
+        thread::sleep(poll);
+        let mut current = stamp(path);
+        if current == last {
+            continue;
+        }
+        loop {
+            thread::sleep(debounce);
+            let settled = stamp(path);
+            if settled == current {
+                break;
+            }
+            current = settled;
+        }
+        last = current;
+        if on_change().is_break() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_burst_of_writes_triggers_once() {
+        let path = std::env::temp_dir().join(format!("pngme-{}-watch_burst", std::process::id()));
+        std::fs::write(&path, b"start").unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        let watched = path.clone();
+        thread::spawn(move || {
+            let mut runs = 0;
+            watch(&watched, Duration::from_millis(20), Duration::from_millis(150), || {
+                runs += 1;
+                sender.send(runs).unwrap();
+                if runs == 2 { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+            });
+        });
+
+        thread::sleep(Duration::from_millis(60));
+        for content in ["a", "ab", "abc"] {
+            std::fs::write(&path, content).unwrap();
+            thread::sleep(Duration::from_millis(30));
+        }
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)), Ok(1));
+        assert!(receiver.recv_timeout(Duration::from_millis(300)).is_err());
+
+        std::fs::write(&path, b"later").unwrap();
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)), Ok(2));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,441 @@
+use thiserror::Error;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769,
+    1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+/// The order code length code lengths are stored in, for dynamic Huffman blocks.
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+const WINDOW_SIZE: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const MAX_CHAIN: usize = 64;
+const HASH_SIZE: usize = 1 << 15;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ZlibError {
+    #[error("Invalid zlib header")]
+    InvalidHeader,
+    #[error("Compressed data ends unexpectedly")]
+    UnexpectedEnd,
+    #[error("Invalid deflate block type")]
+    InvalidBlockType,
+    #[error("Stored block length does not match its complement")]
+    InvalidStoredLength,
+    #[error("Invalid Huffman code")]
+    InvalidCode,
+    #[error("Back-reference reaches before the start of the data")]
+    InvalidDistance,
+    #[error("Adler-32 checksum does not match")]
+    ChecksumMismatch,
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+/// Wraps `data` in a zlib stream made of uncompressed (stored) deflate blocks.
+pub fn compress_stored(data: &[u8]) -> Vec<u8> {
+    let mut res = vec!(0x78, 0x01);
+    let mut blocks = data.chunks(u16::MAX as usize).peekable();
+    if blocks.peek().is_none() {
+        res.extend([1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let len = block.len() as u16;
+        res.push(if blocks.peek().is_none() { 1 } else { 0 });
+        res.extend(len.to_le_bytes());
+        res.extend((!len).to_le_bytes());
+        res.extend(block);
+    }
+    res.extend(adler32(data).to_be_bytes());
+    res
+}
+
+/// Compresses `data` into a zlib stream.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut res = vec!(0x78, 0x9c);
+    res.extend(deflate(data));
+    res.extend(adler32(data).to_be_bytes());
+    res
+}
+
+/// Decompresses a zlib stream, checking its header and Adler-32 checksum.
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, ZlibError> {
+    if data.len() < 2 || data[0] & 0x0f != 8 || !u16::from_be_bytes([data[0], data[1]]).is_multiple_of(31) || data[1] & 0x20 != 0 {
+        return Err(ZlibError::InvalidHeader);
+    }
+    let (res, consumed) = inflate_with_len(&data[2..])?;
+    let checksum = data.get(2 + consumed..2 + consumed + 4).ok_or(ZlibError::UnexpectedEnd)?;
+    if u32::from_be_bytes(checksum.try_into().unwrap()) != adler32(&res) {
+        return Err(ZlibError::ChecksumMismatch);
+    }
+    Ok(res)
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    /// Writes the low `count` bits of `value`, least significant first.
+    fn write(&mut self, value: u32, count: u32) {
+        self.buffer |= value << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Writes a Huffman code, which goes most significant bit first.
+    fn write_code(&mut self, code: u32, len: u32) {
+        self.write(code.reverse_bits() >> (32 - len), len);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+fn write_fixed_literal(writer: &mut BitWriter, symbol: u16) {
+    let symbol = symbol as u32;
+    match symbol {
+        0..=143 => writer.write_code(0x30 + symbol, 8),
+        144..=255 => writer.write_code(0x190 + symbol - 144, 9),
+        256..=279 => writer.write_code(symbol - 256, 7),
+        _ => writer.write_code(0xc0 + symbol - 280, 8),
+    }
+}
+
+/// The index of the last entry in `bases` that is at most `value`.
+fn base_index(bases: &[u16], value: usize) -> usize {
+    bases.iter().rposition(|base| *base as usize <= value).unwrap()
+}
+
+fn hash(data: &[u8], pos: usize) -> usize {
+    let value = (data[pos] as usize) << 16 | (data[pos + 1] as usize) << 8 | data[pos + 2] as usize;
+    (value.wrapping_mul(2654435761) >> 8) % HASH_SIZE
+}
+
+/// Compresses `data` into a raw deflate stream: a single fixed-Huffman block with greedy LZ77 matching.
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter { bytes: vec!(), buffer: 0, count: 0 };
+    writer.write(1, 1);
+    writer.write(1, 2);
+
+    // hash chains: `head` holds the latest position for each hash, `prev` links to older ones
+    let mut head = vec!(usize::MAX; HASH_SIZE);
+    let mut prev = vec!(usize::MAX; data.len());
+    let insert = |head: &mut [usize], prev: &mut [usize], pos: usize| {
+        if pos + MIN_MATCH <= data.len() {
+            let key = hash(data, pos);
+            prev[pos] = head[key];
+            head[key] = pos;
+        }
+    };
+
+    let mut pos = 0;
+    while pos < data.len() {
+        let mut best = (0, 0);
+        if pos + MIN_MATCH <= data.len() {
+            let mut candidate = head[hash(data, pos)];
+            let mut chain = 0;
+            while candidate != usize::MAX && pos - candidate <= WINDOW_SIZE && chain < MAX_CHAIN {
+                let len = data[candidate..].iter()
+                    .zip(&data[pos..])
+                    .take(MAX_MATCH)
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if len > best.0 {
+                    best = (len, pos - candidate);
+                }
+                candidate = prev[candidate];
+                chain += 1;
+            }
+        }
+
+        let (len, distance) = best;
+        if len >= MIN_MATCH {
+            let idx = base_index(&LENGTH_BASE, len);
+            write_fixed_literal(&mut writer, 257 + idx as u16);
+            writer.write((len - LENGTH_BASE[idx] as usize) as u32, LENGTH_EXTRA[idx] as u32);
+
+            let idx = base_index(&DISTANCE_BASE, distance);
+            writer.write_code(idx as u32, 5);
+            writer.write((distance - DISTANCE_BASE[idx] as usize) as u32, DISTANCE_EXTRA[idx] as u32);
+
+            for _ in 0..len {
+                insert(&mut head, &mut prev, pos);
+                pos += 1;
+            }
+        }
+        else {
+            write_fixed_literal(&mut writer, data[pos] as u16);
+            insert(&mut head, &mut prev, pos);
+            pos += 1;
+        }
+    }
+
+    write_fixed_literal(&mut writer, 256);
+    writer.finish()
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, count: u32) -> Result<u32, ZlibError> {
+        while self.count < count {
+            let byte = *self.data.get(self.pos).ok_or(ZlibError::UnexpectedEnd)?;
+            self.pos += 1;
+            self.buffer |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1u64 << count) - 1) as u32;
+        self.buffer >>= count;
+        self.count -= count;
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+/// A canonical Huffman code: how many codes have each length, and the symbols in code order.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0; 16];
+        for len in lengths {
+            counts[*len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut symbols: Vec<(u8, u16)> = lengths.iter()
+            .enumerate()
+            .filter(|(_, len)| **len > 0)
+            .map(|(symbol, len)| (*len, symbol as u16))
+            .collect();
+        symbols.sort();
+        Huffman { counts, symbols: symbols.into_iter().map(|(_, symbol)| symbol).collect() }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, ZlibError> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(ZlibError::InvalidCode)
+    }
+}
+
+fn fixed_tables() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_tables(reader: &mut BitReader) -> Result<(Huffman, Huffman), ZlibError> {
+    let literals = reader.bits(5)? as usize + 257;
+    let distances = reader.bits(5)? as usize + 1;
+    let code_lengths = reader.bits(4)? as usize + 4;
+
+    let mut lengths = [0u8; 19];
+    for idx in CODE_LENGTH_ORDER.iter().take(code_lengths) {
+        lengths[*idx] = reader.bits(3)? as u8;
+    }
+    let code_length_table = Huffman::new(&lengths);
+
+    let mut lengths = vec!();
+    while lengths.len() < literals + distances {
+        let (value, repeat) = match code_length_table.decode(reader)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => (*lengths.last().ok_or(ZlibError::InvalidCode)?, 3 + reader.bits(2)?),
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() != literals + distances {
+        return Err(ZlibError::InvalidCode);
+    }
+    Ok((Huffman::new(&lengths[..literals]), Huffman::new(&lengths[literals..])))
+}
+
+/// Decompresses a raw deflate stream.
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, ZlibError> {
+    inflate_with_len(data).map(|(res, _)| res)
+}
+
+/// Like [`inflate`], also returning how many input bytes the stream took up.
+pub(crate) fn inflate_with_len(data: &[u8]) -> Result<(Vec<u8>, usize), ZlibError> {
+    let mut reader = BitReader { data, pos: 0, buffer: 0, count: 0 };
+    let mut res = vec!();
+
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let header = data.get(reader.pos..reader.pos + 4).ok_or(ZlibError::UnexpectedEnd)?;
+                let len = u16::from_le_bytes([header[0], header[1]]);
+                if len != !u16::from_le_bytes([header[2], header[3]]) {
+                    return Err(ZlibError::InvalidStoredLength);
+                }
+                reader.pos += 4;
+                let block = data.get(reader.pos..reader.pos + len as usize).ok_or(ZlibError::UnexpectedEnd)?;
+                res.extend_from_slice(block);
+                reader.pos += len as usize;
+            },
+            block_type @ (1 | 2) => {
+                let (literals, distances) = if block_type == 1 { fixed_tables() } else { dynamic_tables(&mut reader)? };
+                loop {
+                    let symbol = literals.decode(&mut reader)? as usize;
+                    match symbol {
+                        0..=255 => res.push(symbol as u8),
+                        256 => break,
+                        _ => {
+                            let idx = symbol - 257;
+                            if idx >= LENGTH_BASE.len() {
+                                return Err(ZlibError::InvalidCode);
+                            }
+                            let len = LENGTH_BASE[idx] as usize + reader.bits(LENGTH_EXTRA[idx] as u32)? as usize;
+
+                            let idx = distances.decode(&mut reader)? as usize;
+                            if idx >= DISTANCE_BASE.len() {
+                                return Err(ZlibError::InvalidCode);
+                            }
+                            let distance = DISTANCE_BASE[idx] as usize + reader.bits(DISTANCE_EXTRA[idx] as u32)? as usize;
+                            if distance > res.len() {
+                                return Err(ZlibError::InvalidDistance);
+                            }
+
+                            let start = res.len() - distance;
+                            for offset in 0..len {
+                                res.push(res[start + offset]);
+                            }
+                        }
+                    }
+                }
+            },
+            _ => return Err(ZlibError::InvalidBlockType)
+        }
+
+        if last {
+            return Ok((res, reader.pos));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples() -> Vec<Vec<u8>> {
+        vec![
+            vec!(),
+            b"a".to_vec(),
+            b"hello hello hello hello".to_vec(),
+            "Grüße, 世界! 🦀 ".repeat(50).into_bytes(),
+            (0..=255).cycle().take(100_000).collect(),
+            vec!(0; 70_000),
+        ]
+    }
+
+    #[test]
+    fn test_adler32() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+        assert_eq!(adler32(b""), 1);
+    }
+
+    #[test]
+    fn test_stored_blocks() {
+        let data = vec!(7; 70_000);
+        let stream = compress_stored(&data);
+        // header, two block headers, data and checksum
+        assert_eq!(stream.len(), 2 + 5 * 2 + data.len() + 4);
+        assert_eq!(stream[2], 0);
+        assert_eq!(decompress(&stream).unwrap(), data);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for data in samples() {
+            assert_eq!(decompress(&compress(&data)).unwrap(), data);
+            assert_eq!(inflate(&deflate(&data)).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn test_compress_shrinks_repetitive_data() {
+        assert!(compress(&vec!(0; 70_000)).len() < 1_000);
+    }
+
+    #[test]
+    fn test_decompress_dynamic_huffman() {
+        // zlib.compress(MESSAGE, 9) from python, which picks a dynamic Huffman block
+        let message = [b"pngme hides messages in png chunks. ".repeat(3), b"the quick brown fox jumps over the lazy dog 0123456789".to_vec()].concat();
+        let stream = [
+            120, 218, 173, 202, 209, 17, 130, 48, 16, 69, 209, 86, 94, 5, 142, 160, 2, 150, 131, 100, 73, 66, 204, 110,
+            100, 9, 2, 213, 35, 214, 192, 223, 153, 185, 55, 177, 141, 4, 231, 13, 41, 34, 169, 182, 246, 7, 207, 72,
+            108, 209, 185, 204, 65, 47, 135, 79, 121, 38, 71, 248, 100, 223, 5, 188, 70, 249, 50, 122, 89, 48, 228,
+            152, 20, 50, 211, 248, 207, 239, 118, 91, 97, 196, 226, 90, 148, 183, 251, 163, 170, 155, 231, 14, 211, 169, 57, 93,
+        ];
+        assert_eq!(stream[2] >> 1 & 3, 2);
+        assert_eq!(decompress(&stream).unwrap(), message);
+    }
+
+    #[test]
+    fn test_decompress_errors() {
+        assert_eq!(decompress(&[0x78]), Err(ZlibError::InvalidHeader));
+        assert_eq!(decompress(&[0x78, 0x9d, 0x03, 0x00]), Err(ZlibError::InvalidHeader));
+
+        let mut stream = compress(b"checksummed");
+        let last = stream.len() - 1;
+        stream[last] ^= 1;
+        assert_eq!(decompress(&stream), Err(ZlibError::ChecksumMismatch));
+
+        let stream = compress(b"truncated data");
+        assert_eq!(decompress(&stream[..stream.len() - 6]), Err(ZlibError::UnexpectedEnd));
+        assert_eq!(inflate(&[0x07]), Err(ZlibError::InvalidBlockType));
+    }
+}
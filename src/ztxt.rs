@@ -0,0 +1,124 @@
+use thiserror::Error;
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, itxt::{self, ITxtError}, zlib::{self, ZlibError}};
+
+/// The contents of a `zTXt` chunk: a Latin-1 keyword and zlib-compressed Latin-1 text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressedText {
+    pub keyword: String,
+    pub text: String,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ZTxtError {
+    #[error("Keyword must be 1-79 printable Latin-1 characters")]
+    InvalidKeyword,
+    #[error("Missing null separator after the keyword")]
+    MissingSeparator,
+    #[error("Missing compression method")]
+    MissingCompressionMethod,
+    #[error("Unknown compression method {0}")]
+    UnknownCompressionMethod(u8),
+    #[error("Text must be Latin-1")]
+    NotLatin1,
+    #[error("Invalid compressed text: {0}")]
+    Zlib(#[from] ZlibError),
+    #[error("Chunk is not a zTXt chunk")]
+    NotZTxt,
+}
+
+impl From<ITxtError> for ZTxtError {
+    fn from(err: ITxtError) -> Self {
+        match err {
+            ITxtError::MissingSeparator(_) => ZTxtError::MissingSeparator,
+            _ => ZTxtError::InvalidKeyword,
+        }
+    }
+}
+
+impl CompressedText {
+    pub fn new(keyword: &str, text: &str) -> CompressedText {
+        CompressedText { keyword: keyword.to_string(), text: text.to_string() }
+    }
+
+    pub fn chunk_type() -> ChunkType {
+        ChunkType::parse("zTXt").unwrap()
+    }
+
+    /// Lays the fields out as `keyword\0 method compressed-text`, with method 0 (zlib).
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ZTxtError> {
+        if !itxt::is_valid_keyword(&self.keyword) {
+            return Err(ZTxtError::InvalidKeyword);
+        }
+        let text = self.text.chars()
+            .map(|c| u8::try_from(c).map_err(|_| ZTxtError::NotLatin1))
+            .collect::<Result<Vec<u8>, _>>()?;
+
+        let mut res: Vec<u8> = self.keyword.chars().map(|c| c as u8).collect();
+        res.push(0);
+        res.push(0);
+        res.extend(zlib::compress(&text));
+        Ok(res)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<CompressedText, ZTxtError> {
+        let (keyword, rest) = itxt::split_keyword(data)?;
+        let (method, text) = rest.split_first().ok_or(ZTxtError::MissingCompressionMethod)?;
+        if *method != 0 {
+            return Err(ZTxtError::UnknownCompressionMethod(*method));
+        }
+        let text = zlib::decompress(text)?.iter().map(|byte| *byte as char).collect();
+        Ok(CompressedText { keyword, text })
+    }
+
+    pub fn to_chunk(&self) -> Result<Chunk, ZTxtError> {
+        Ok(Chunk::new(Self::chunk_type(), self.to_bytes()?))
+    }
+}
+
+impl TryFrom<&Chunk> for CompressedText {
+    type Error = ZTxtError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        if *chunk.chunk_type() != Self::chunk_type() {
+            return Err(ZTxtError::NotZTxt);
+        }
+        CompressedText::from_bytes(chunk.data())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_long_value_round_trip_is_compressed() {
+        let text = CompressedText::new("Comment", &"Ærøskøbing harbour at dawn. ".repeat(100));
+        let bytes = text.to_bytes().unwrap();
+
+        assert!(bytes.len() < text.text.chars().count() / 4);
+        assert_eq!(&bytes[..9], b"Comment\0\0");
+        assert_eq!(zlib::decompress(&bytes[9..]).unwrap()[..2], [0xc6, b'r']);
+        assert_eq!(CompressedText::from_bytes(&bytes).unwrap(), text);
+    }
+
+    #[test]
+    fn test_chunk_round_trip() {
+        let text = CompressedText::new("Author", "Jane Doe");
+        let chunk = text.to_chunk().unwrap();
+        assert_eq!(chunk.chunk_type().to_string(), "zTXt");
+        assert_eq!(CompressedText::try_from(&chunk).unwrap(), text);
+        assert_eq!(CompressedText::try_from(&Chunk::new(ChunkType::parse("tEXt").unwrap(), vec![])), Err(ZTxtError::NotZTxt));
+    }
+
+    #[test]
+    fn test_invalid_layouts() {
+        assert_eq!(CompressedText::from_bytes(b"Title"), Err(ZTxtError::MissingSeparator));
+        assert_eq!(CompressedText::from_bytes(b"\0\0"), Err(ZTxtError::InvalidKeyword));
+        assert_eq!(CompressedText::from_bytes(b"Title\0"), Err(ZTxtError::MissingCompressionMethod));
+        assert_eq!(CompressedText::from_bytes(b"Title\0\x01"), Err(ZTxtError::UnknownCompressionMethod(1)));
+        assert!(matches!(CompressedText::from_bytes(b"Title\0\0junk"), Err(ZTxtError::Zlib(_))));
+        assert_eq!(CompressedText::new("Title", "smile 🙂").to_bytes(), Err(ZTxtError::NotLatin1));
+    }
+}
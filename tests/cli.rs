@@ -0,0 +1,69 @@
+use std::process::{Command, Output};
+
+const GRADIENT: &[u8] = include_bytes!("fixtures/gradient.png");
+
+fn pngme(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_pngme")).args(args).output().unwrap()
+}
+
+fn fixture_copy(name: &str) -> String {
+    let path = std::env::temp_dir()
+        .join(format!("pngme-cli-{}-{}", std::process::id(), name))
+        .to_string_lossy()
+        .into_owned();
+    std::fs::write(&path, GRADIENT).unwrap();
+    path
+}
+
+#[test]
+fn test_decode_exit_codes() {
+    let path = fixture_copy("decode_exit_codes.png");
+
+    let found = pngme(&["decode", &path, "tEXt"]);
+    assert!(found.status.success());
+
+    let missing = pngme(&["decode", &path, "ruSt"]);
+    assert_eq!(missing.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&missing.stderr).contains("Nothing to decode"));
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_decode_count() {
+    let path = fixture_copy("decode_count.png");
+
+    let found = pngme(&["decode", &path, "tEXt", "--count"]);
+    assert!(found.status.success());
+    assert_eq!(String::from_utf8_lossy(&found.stdout), "1\n");
+
+    let missing = pngme(&["decode", &path, "ruSt", "--count"]);
+    assert_eq!(missing.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&missing.stdout), "0\n");
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_timing_reports_phases_without_changing_output() {
+    let path = fixture_copy("timing.png");
+    let plain = format!("{}.plain.png", path);
+    let timed = format!("{}.timed.png", path);
+
+    let output = pngme(&["encode", &path, "ruSt", "message", &plain]);
+    assert!(output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("[timing]"));
+
+    let output = pngme(&["encode", &path, "ruSt", "message", &timed, "--timing"]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    for phase in ["read", "parse", "encode", "write"] {
+        assert!(stderr.contains(&format!("[timing] {}: ", phase)), "missing {} in {}", phase, stderr);
+    }
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("[timing]"));
+
+    assert_eq!(std::fs::read(&plain).unwrap(), std::fs::read(&timed).unwrap());
+    for file in [path, plain, timed] {
+        std::fs::remove_file(file).unwrap();
+    }
+}
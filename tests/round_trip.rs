@@ -0,0 +1,25 @@
+use pngme::png::Png;
+
+const GRADIENT: &[u8] = include_bytes!("fixtures/gradient.png");
+
+#[test]
+fn test_load_save_is_lossless() {
+    let png = Png::try_from(GRADIENT).unwrap();
+    assert_eq!(png.as_bytes(), GRADIENT);
+}
+
+#[test]
+fn test_round_trip_preserves_chunk_order() {
+    let png = Png::try_from(GRADIENT).unwrap();
+    let types: Vec<String> = png.iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+    assert_eq!(types, ["IHDR", "tEXt", "IDAT", "IEND"]);
+    assert!(png.iter().all(|chunk| chunk.checksum_matches()));
+}
+
+#[test]
+fn test_edit_then_revert_is_lossless() {
+    let mut png = Png::try_from(GRADIENT).unwrap();
+    let text = png.remove_chunk("tEXt").unwrap();
+    png.insert_before_idat(text).unwrap();
+    assert_eq!(png.as_bytes(), GRADIENT);
+}